@@ -3,13 +3,13 @@ use bevy::prelude::*;
 use lightyear::prelude::*;
 use lightyear::prelude::server::ClientOf;
 use shared::bot::BotShip;
-use shared::ships::get_shared_ship_components;
+use shared::ships::{get_shared_ship_components, Ship, ShipColliders, ShipsData};
 
 pub(crate) struct BotPlugin;
 
 impl Plugin for BotPlugin {
     fn build(&self, app: &mut App) {
-        app.add_observer(add_bot_collider);
+        app.add_systems(Update, add_bot_collider.run_if(resource_exists::<ShipsData>));
         // Debug bot position (we run this in Last to be after Interpolation)
         // app.add_systems(Last, debug_bot_position);
     }
@@ -31,13 +31,16 @@ fn debug_bot_position(
 /// When an interpolated bot is spawned, we add a collider to it so we can visually
 /// find collisions between bullets and bots
 fn add_bot_collider(
-    trigger: Trigger<OnAdd, BotShip>,
     mut commands: Commands,
-    query: Query<(), With<Interpolated>>,
+    ships_data: Res<ShipsData>,
+    ship_colliders: Res<ShipColliders>,
+    query: Query<(Entity, &Ship), (With<BotShip>, With<Interpolated>, Without<Collider>)>,
 ) {
-    if query.get(trigger.target()).is_ok() {
-        commands.entity(trigger.target()).insert(
-            get_shared_ship_components(Collider::sphere(0.5))
+    for (entity, ship) in query.iter() {
+        let Some(ship_data) = ships_data.ships.get(&ship.0) else { continue };
+        let Some(collider) = ship_colliders.get(ship.0) else { continue };
+        commands.entity(entity).insert(
+            get_shared_ship_components(collider, &ship_data.physics)
         );
     }
 }
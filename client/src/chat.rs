@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+use lightyear::connection::host::HostClient;
+use lightyear::prelude::*;
+use shared::prelude::{AppState, ChatChannel, ChatMessage, PlayingSubState, SendChatMessage};
+
+use crate::menu::{disable_player_input_system, enable_player_input_system};
+
+/// Carries the chat between the chat box and the server
+pub(crate) struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(send_chat_message);
+        app.add_systems(PreUpdate, receive_chat_messages.after(MessageSet::Receive));
+        app.add_systems(OnEnter(AppState::Playing(PlayingSubState::Chat)), disable_player_input_system);
+        app.add_systems(OnExit(AppState::Playing(PlayingSubState::Chat)), enable_player_input_system);
+    }
+}
+
+fn send_chat_message(
+    trigger: Trigger<SendChatMessage>,
+    client: Single<&mut MessageSender<ChatMessage>, (With<Client>, With<Connected>)>,
+) {
+    let message = ChatMessage {
+        // the server knows who we are
+        author: None,
+        scope: trigger.scope,
+        text: trigger.text.clone(),
+    };
+    client.into_inner().send::<ChatChannel>(message);
+}
+
+/// Trigger the messages from the server so that the chat box can show them
+fn receive_chat_messages(
+    mut commands: Commands,
+    // the host-client already sees the messages triggered by the server
+    mut receiver: Single<&mut MessageReceiver<ChatMessage>, (With<Client>, With<Connected>, Without<HostClient>)>,
+) {
+    receiver.receive().for_each(|message| commands.trigger(message));
+}
@@ -0,0 +1,164 @@
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+use shared::prelude::PlayerInput;
+
+use crate::input::LookSettings;
+
+/// Analog sticks and HOTAS axes.
+/// Gamepad buttons are bound like keys, see [`crate::input::Binding`].
+pub(crate) struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_axis_processor::<ResponseCurve>();
+        app.register_dual_axis_processor::<ResponseCurve>();
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+impl Stick {
+    fn gamepad_stick(&self) -> GamepadStick {
+        match self {
+            Stick::Left => GamepadStick::LEFT,
+            Stick::Right => GamepadStick::RIGHT,
+        }
+    }
+}
+
+/// A single gamepad axis, for example the throttle or the twist of a HOTAS
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AxisBinding {
+    pub axis: GamepadAxis,
+    #[serde(default)]
+    pub inverted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GamepadSettings {
+    /// Stick used for the strafe (x) and forward (y) thrust
+    pub move_stick: Option<Stick>,
+    /// Stick used to turn the ship
+    pub look_stick: Option<Stick>,
+    /// Axis used for the up/down thrust
+    pub vertical_axis: Option<AxisBinding>,
+    /// Axis used to roll the ship
+    pub roll_axis: Option<AxisBinding>,
+    /// Deflection around the center that is ignored, between 0.0 and 1.0
+    pub dead_zone: f32,
+    /// Exponent applied to the deflection after the dead zone.
+    /// 1.0 is linear, higher values give finer control around the center.
+    pub response_curve: f32,
+    /// How fast the look stick turns the ship, a fully deflected stick turns as much per frame as moving the mouse by this many pixels
+    pub look_speed: f32,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            move_stick: Some(Stick::Left),
+            look_stick: Some(Stick::Right),
+            vertical_axis: None,
+            roll_axis: None,
+            dead_zone: 0.15,
+            response_curve: 2.0,
+            look_speed: 10.0,
+        }
+    }
+}
+
+impl GamepadSettings {
+    pub(crate) fn add_to_input_map(&self, look: &LookSettings, input_map: &mut InputMap<PlayerInput>) {
+        let curve = ResponseCurve(self.response_curve);
+        if let Some(stick) = self.move_stick {
+            input_map.insert_dual_axis(
+                PlayerInput::Move,
+                stick.gamepad_stick()
+                    .with_circle_deadzone(self.dead_zone)
+                    .with_processor(curve),
+            );
+        }
+        if let Some(stick) = self.look_stick {
+            // pushing the stick up should pitch up, like moving the mouse up
+            input_map.insert_dual_axis(
+                PlayerInput::Look,
+                look.apply(
+                    stick.gamepad_stick()
+                        .with_circle_deadzone(self.dead_zone)
+                        .with_processor(curve)
+                        .inverted_y(),
+                    self.look_speed,
+                ),
+            );
+        }
+        for (action, binding) in [(PlayerInput::MoveVertical, self.vertical_axis), (PlayerInput::Roll, self.roll_axis)] {
+            let Some(binding) = binding else { continue };
+            let axis = GamepadControlAxis::new(binding.axis)
+                .with_deadzone_symmetric(self.dead_zone)
+                .with_processor(curve);
+            if binding.inverted {
+                input_map.insert_axis(action, axis.inverted());
+            } else {
+                input_map.insert_axis(action, axis);
+            }
+        }
+    }
+
+    /// Sliders for the analog settings. Returns true when the player finished editing a value.
+    pub(crate) fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut finished = false;
+        egui::Grid::new("gamepad").show(ui, |ui| {
+            for (label, value, range) in [
+                ("Dead zone", &mut self.dead_zone, 0.0..=0.9),
+                ("Response curve", &mut self.response_curve, 1.0..=4.0),
+                ("Look speed", &mut self.look_speed, 1.0..=50.0),
+            ] {
+                ui.label(label);
+                let response = ui.add(egui::Slider::new(value, range));
+                finished |= response.drag_stopped() || (response.changed() && !response.dragged());
+                ui.end_row();
+            }
+        });
+        ui.label("The sticks and HOTAS axes can be changed in the settings file.");
+        finished
+    }
+}
+
+/// Raises the deflection of an axis or stick to a power, keeping its direction
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct ResponseCurve(pub f32);
+
+impl Eq for ResponseCurve {}
+
+impl Hash for ResponseCurve {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+#[serde_typetag]
+impl CustomAxisProcessor for ResponseCurve {
+    fn process(&self, input_value: f32) -> f32 {
+        input_value.signum() * input_value.abs().min(1.0).powf(self.0)
+    }
+}
+
+#[serde_typetag]
+impl CustomDualAxisProcessor for ResponseCurve {
+    fn process(&self, input_value: Vec2) -> Vec2 {
+        let length = input_value.length();
+        if length == 0.0 {
+            return Vec2::ZERO;
+        }
+        input_value / length * length.min(1.0).powf(self.0)
+    }
+}
@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use lightyear::connection::host::HostClient;
+use lightyear::prelude::*;
+use shared::prelude::{DamageTaken, ShipImpactEvent};
+
+pub(crate) struct ImpactsPlugin;
+
+impl Plugin for ImpactsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, (
+            receive_ship_impacts,
+            receive_damage_taken,
+        ).after(MessageSet::Receive));
+    }
+}
+
+/// When the server tells us a ship slammed into something, trigger the event on the
+/// entity we actually display (the predicted or interpolated one) so the renderer can react to it.
+fn receive_ship_impacts(
+    mut commands: Commands,
+    // the host-client already sees the event triggered by the server
+    mut receiver: Single<&mut MessageReceiver<ShipImpactEvent>, (With<Client>, With<Connected>, Without<HostClient>)>,
+    confirmed: Query<&Confirmed>,
+) {
+    receiver.receive().for_each(|mut event| {
+        let Ok(confirmed) = confirmed.get(event.entity) else {
+            error!("Could not find Confirmed ship from impact event: {:?}", event);
+            return;
+        };
+        let Some(entity) = confirmed.predicted.or(confirmed.interpolated) else { return };
+        event.entity = entity;
+        commands.trigger(event);
+    });
+}
+
+/// Trigger the damage our ship took, so that the HUD can show where it came from
+fn receive_damage_taken(
+    mut commands: Commands,
+    // the host-client already sees the event triggered by the server
+    mut receiver: Single<&mut MessageReceiver<DamageTaken>, (With<Client>, With<Connected>, Without<HostClient>)>,
+) {
+    receiver.receive().for_each(|damage| commands.trigger(damage));
+}
@@ -0,0 +1,455 @@
+use std::path::{Path, PathBuf};
+
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy_egui::egui;
+use leafwing_input_manager::prelude::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+use shared::prelude::PlayerInput;
+
+use crate::gamepad::GamepadSettings;
+use crate::user_config::user_config_path;
+
+/// Loads the player's input settings from a user config file, applies them live to the
+/// predicted player's [`InputMap`], and provides a screen to rebind the controls.
+pub(crate) struct InputSettingsPlugin;
+
+impl Plugin for InputSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let path = input_settings_path();
+        // older versions saved the settings in the working directory
+        let path = if !path.exists() && Path::new(INPUT_SETTINGS_FILE).exists() {
+            PathBuf::from(INPUT_SETTINGS_FILE)
+        } else {
+            path
+        };
+        app.insert_resource(InputSettings::load(path));
+        app.init_resource::<ControlsMenu>();
+        app.init_resource::<ActionState<PlayerInput>>();
+        app.add_systems(Update, (
+            capture_rebinding_system,
+            apply_input_settings_system,
+        ).chain());
+    }
+}
+
+/// The file the input settings are stored in, in the per-user config directory
+const INPUT_SETTINGS_FILE: &str = "input.ron";
+
+fn input_settings_path() -> PathBuf {
+    user_config_path(INPUT_SETTINGS_FILE)
+}
+
+/// The actions that can be rebound, in the order they are shown in the controls menu.
+/// `Look` is always bound to the mouse.
+const REBINDABLE_ACTIONS: [PlayerInput; 21] = [
+    PlayerInput::MoveForward,
+    PlayerInput::MoveBackward,
+    PlayerInput::MoveLeft,
+    PlayerInput::MoveRight,
+    PlayerInput::MoveUp,
+    PlayerInput::MoveDown,
+    PlayerInput::RollLeft,
+    PlayerInput::RollRight,
+    PlayerInput::ShootPrimary,
+    PlayerInput::AfterBurners,
+    PlayerInput::Dash,
+    PlayerInput::NextWeapon,
+    PlayerInput::PreviousWeapon,
+    PlayerInput::Weapon1,
+    PlayerInput::Weapon2,
+    PlayerInput::Weapon3,
+    PlayerInput::Weapon4,
+    PlayerInput::Weapon5,
+    PlayerInput::ToggleFlightMode,
+    PlayerInput::ToggleMousePointer,
+    PlayerInput::ShowScoreboard,
+];
+
+/// The actions handled by the HUD instead of the ship, they work even while the player has no ship.
+/// Their state is kept in the [`ActionState`] resource.
+const HUD_ACTIONS: [PlayerInput; 2] = [PlayerInput::ToggleMousePointer, PlayerInput::ShowScoreboard];
+
+/// The maximum number of bindings per action
+const MAX_BINDINGS_PER_ACTION: usize = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    ScrollUp,
+    ScrollDown,
+    Gamepad(GamepadButton),
+}
+
+impl Binding {
+    fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{key:?}").trim_start_matches("Key").to_string(),
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::ScrollUp => "Scroll Up".to_string(),
+            Binding::ScrollDown => "Scroll Down".to_string(),
+            Binding::Gamepad(button) => format!("Pad {button:?}"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActionBindings {
+    pub action: PlayerInput,
+    pub bindings: Vec<Binding>,
+}
+
+/// The input settings of the local player, saved in [`INPUT_SETTINGS_FILE`]
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputSettings {
+    pub bindings: Vec<ActionBindings>,
+    #[serde(default)]
+    pub look: LookSettings,
+    #[serde(default)]
+    pub gamepad: GamepadSettings,
+}
+
+/// How the mouse (and the look stick) turns the ship.
+/// The look input is scaled before it is buffered, so the server turns the ship exactly like the client predicted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct LookSettings {
+    pub mouse_sensitivity: f32,
+    /// Multiplier applied on top of the sensitivity when turning left and right
+    pub yaw_scale: f32,
+    /// Multiplier applied on top of the sensitivity when turning up and down
+    pub pitch_scale: f32,
+    pub invert_pitch: bool,
+}
+
+impl Default for LookSettings {
+    fn default() -> Self {
+        Self {
+            mouse_sensitivity: 1.0,
+            yaw_scale: 1.0,
+            pitch_scale: 1.0,
+            invert_pitch: false,
+        }
+    }
+}
+
+impl LookSettings {
+    /// Scale and invert a look input
+    pub(crate) fn apply<T: WithDualAxisProcessingPipelineExt>(&self, input: T, sensitivity: f32) -> T {
+        let input = input
+            .sensitivity_x(sensitivity * self.yaw_scale)
+            .sensitivity_y(sensitivity * self.pitch_scale);
+        if self.invert_pitch {
+            input.inverted_y()
+        } else {
+            input
+        }
+    }
+
+    /// Returns true when the player finished editing a value
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut finished = false;
+        egui::Grid::new("look").show(ui, |ui| {
+            for (label, value, range) in [
+                ("Mouse sensitivity", &mut self.mouse_sensitivity, 0.05..=5.0),
+                ("Yaw scale", &mut self.yaw_scale, 0.1..=2.0),
+                ("Pitch scale", &mut self.pitch_scale, 0.1..=2.0),
+            ] {
+                ui.label(label);
+                let response = ui.add(egui::Slider::new(value, range).logarithmic(true));
+                finished |= response.drag_stopped() || (response.changed() && !response.dragged());
+                ui.end_row();
+            }
+            ui.label("Invert pitch");
+            finished |= ui.checkbox(&mut self.invert_pitch, "").changed();
+            ui.end_row();
+        });
+        finished
+    }
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        let bindings = [
+            (PlayerInput::MoveForward, vec![Binding::Key(KeyCode::KeyW)]),
+            (PlayerInput::MoveBackward, vec![Binding::Key(KeyCode::KeyS)]),
+            (PlayerInput::MoveLeft, vec![Binding::Key(KeyCode::KeyA)]),
+            (PlayerInput::MoveRight, vec![Binding::Key(KeyCode::KeyD)]),
+            (PlayerInput::MoveUp, vec![Binding::Key(KeyCode::Space), Binding::Gamepad(GamepadButton::South)]),
+            (PlayerInput::MoveDown, vec![Binding::Key(KeyCode::ShiftLeft), Binding::Gamepad(GamepadButton::East)]),
+            (PlayerInput::RollLeft, vec![Binding::Key(KeyCode::KeyQ), Binding::Gamepad(GamepadButton::LeftTrigger)]),
+            (PlayerInput::RollRight, vec![Binding::Key(KeyCode::KeyE), Binding::Gamepad(GamepadButton::RightTrigger)]),
+            (PlayerInput::ShootPrimary, vec![Binding::Mouse(MouseButton::Left), Binding::Gamepad(GamepadButton::RightTrigger2)]),
+            (PlayerInput::AfterBurners, vec![Binding::Mouse(MouseButton::Right), Binding::Gamepad(GamepadButton::LeftTrigger2)]),
+            (PlayerInput::Dash, vec![Binding::Key(KeyCode::AltLeft), Binding::Gamepad(GamepadButton::West)]),
+            (PlayerInput::NextWeapon, vec![Binding::ScrollUp, Binding::Gamepad(GamepadButton::DPadRight)]),
+            (PlayerInput::PreviousWeapon, vec![Binding::ScrollDown, Binding::Gamepad(GamepadButton::DPadLeft)]),
+            (PlayerInput::Weapon1, vec![Binding::Key(KeyCode::Digit1), Binding::Gamepad(GamepadButton::DPadUp)]),
+            (PlayerInput::Weapon2, vec![Binding::Key(KeyCode::Digit2), Binding::Gamepad(GamepadButton::DPadDown)]),
+            (PlayerInput::Weapon3, vec![Binding::Key(KeyCode::Digit3)]),
+            (PlayerInput::Weapon4, vec![Binding::Key(KeyCode::Digit4)]),
+            (PlayerInput::Weapon5, vec![Binding::Key(KeyCode::Digit5)]),
+            (PlayerInput::ToggleFlightMode, vec![Binding::Key(KeyCode::KeyF), Binding::Gamepad(GamepadButton::North)]),
+            (PlayerInput::ToggleMousePointer, vec![Binding::Key(KeyCode::Backquote), Binding::Gamepad(GamepadButton::Start)]),
+            (PlayerInput::ShowScoreboard, vec![Binding::Key(KeyCode::Tab), Binding::Gamepad(GamepadButton::Select)]),
+        ];
+        Self {
+            bindings: bindings.into_iter()
+                .map(|(action, bindings)| ActionBindings { action, bindings })
+                .collect(),
+            look: LookSettings::default(),
+            gamepad: GamepadSettings::default(),
+        }
+    }
+}
+
+impl InputSettings {
+    /// Load the settings from the given file, falling back to the defaults if the file doesn't exist or is invalid.
+    /// Actions that are missing from the file (for example because they were added in a newer version) get their default bindings,
+    /// which are taken away from the actions that used them until now.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            info!("No input settings found at {}, using the defaults", path.display());
+            return Self::default();
+        };
+        let mut settings = match ron::from_str::<InputSettings>(&contents) {
+            Ok(settings) => settings,
+            Err(e) => {
+                error!("Failed to parse the input settings at {}, using the defaults: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        let defaults = Self::default();
+        for default in defaults.bindings.iter() {
+            if settings.get(&default.action).is_some() {
+                continue;
+            }
+            for other in settings.bindings.iter_mut() {
+                let had_bindings = !other.bindings.is_empty();
+                other.bindings.retain(|binding| !default.bindings.contains(binding));
+                // don't leave an action the player used without any binding
+                if had_bindings && other.bindings.is_empty() {
+                    other.bindings = defaults.get(&other.action).unwrap_or_default().iter()
+                        .filter(|binding| !default.bindings.contains(binding))
+                        .copied()
+                        .collect();
+                }
+            }
+            settings.bindings.push(default.clone());
+        }
+        settings
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let contents = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(contents) => contents,
+            Err(e) => {
+                error!("Failed to serialize the input settings: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = std::fs::write(path, contents) {
+            error!("Failed to save the input settings to {}: {}", path.display(), e);
+        }
+    }
+
+    pub fn get(&self, action: &PlayerInput) -> Option<&[Binding]> {
+        self.bindings.iter()
+            .find(|bindings| bindings.action == *action)
+            .map(|bindings| bindings.bindings.as_slice())
+    }
+
+    fn get_mut(&mut self, action: &PlayerInput) -> &mut Vec<Binding> {
+        if let Some(index) = self.bindings.iter().position(|bindings| bindings.action == *action) {
+            &mut self.bindings[index].bindings
+        } else {
+            self.bindings.push(ActionBindings { action: *action, bindings: vec![] });
+            &mut self.bindings.last_mut().unwrap().bindings
+        }
+    }
+
+    /// The other actions that use the same binding
+    pub fn conflicts(&self, action: &PlayerInput, binding: &Binding) -> Vec<PlayerInput> {
+        self.bindings.iter()
+            .filter(|bindings| bindings.action != *action && bindings.bindings.contains(binding))
+            .map(|bindings| bindings.action)
+            .collect()
+    }
+
+    /// The input map of the ship the player controls
+    pub fn input_map(&self) -> InputMap<PlayerInput> {
+        let mut input_map = self.button_input_map(|action| !HUD_ACTIONS.contains(action));
+        input_map.insert_dual_axis(PlayerInput::Look, self.look.apply(MouseMove::default(), self.look.mouse_sensitivity));
+        self.gamepad.add_to_input_map(&self.look, &mut input_map);
+        input_map
+    }
+
+    /// The input map of the [`HUD_ACTIONS`]
+    pub fn hud_input_map(&self) -> InputMap<PlayerInput> {
+        self.button_input_map(|action| HUD_ACTIONS.contains(action))
+    }
+
+    fn button_input_map(&self, filter: impl Fn(&PlayerInput) -> bool) -> InputMap<PlayerInput> {
+        let mut input_map = InputMap::<PlayerInput>::default();
+        for ActionBindings { action, bindings } in self.bindings.iter().filter(|bindings| filter(&bindings.action)) {
+            for binding in bindings {
+                match binding {
+                    Binding::Key(key) => { input_map.insert(*action, *key); }
+                    Binding::Mouse(button) => { input_map.insert(*action, *button); }
+                    Binding::ScrollUp => { input_map.insert(*action, MouseScrollDirection::UP); }
+                    Binding::ScrollDown => { input_map.insert(*action, MouseScrollDirection::DOWN); }
+                    Binding::Gamepad(button) => { input_map.insert(*action, *button); }
+                }
+            }
+        }
+        input_map
+    }
+}
+
+/// The state of the controls tab of the menu
+#[derive(Resource, Default, Debug)]
+pub(crate) struct ControlsMenu {
+    /// The action (and binding slot) waiting for the player to press something
+    rebinding: Option<(PlayerInput, usize)>,
+}
+
+impl ControlsMenu {
+    pub(crate) fn is_rebinding(&self) -> bool {
+        self.rebinding.is_some()
+    }
+
+    pub(crate) fn cancel(&mut self) {
+        self.rebinding = None;
+    }
+}
+
+/// The rebinding screen. Click on a binding and press the new key or button to rebind it.
+/// Bindings used by several actions are shown in red.
+pub(crate) fn controls_ui(ui: &mut egui::Ui, menu: &mut ControlsMenu, settings: &mut ResMut<InputSettings>) {
+    egui::Grid::new("controls").striped(true).show(ui, |ui| {
+        for action in REBINDABLE_ACTIONS.iter() {
+            ui.label(format!("{action:?}"));
+            let bindings = settings.get(action).unwrap_or_default().to_vec();
+            for slot in 0..MAX_BINDINGS_PER_ACTION {
+                let waiting = menu.rebinding == Some((*action, slot));
+                let binding = bindings.get(slot);
+                let label = if waiting {
+                    egui::RichText::new("Press a button...")
+                } else if let Some(binding) = binding {
+                    let text = egui::RichText::new(binding.label());
+                    if settings.conflicts(action, binding).is_empty() {
+                        text
+                    } else {
+                        text.color(egui::Color32::RED)
+                    }
+                } else {
+                    egui::RichText::new("-").weak()
+                };
+                let button = ui.button(label);
+                if let Some(binding) = binding {
+                    let conflicts = settings.conflicts(action, binding);
+                    if !conflicts.is_empty() {
+                        button.clone().on_hover_text(format!("Also bound to {conflicts:?}"));
+                    }
+                }
+                if button.clicked() {
+                    menu.rebinding = Some((*action, slot));
+                }
+                if button.secondary_clicked() && slot < bindings.len() {
+                    // right click clears the binding
+                    settings.get_mut(action).remove(slot);
+                    settings.save(input_settings_path());
+                }
+            }
+            ui.end_row();
+        }
+    });
+    ui.separator();
+    ui.label("Left click to rebind, right click to clear, Escape to cancel.");
+    ui.separator();
+    ui.heading("Look");
+    // edit copies so that the input map is only rebuilt when a value actually changed
+    let mut look = settings.look.clone();
+    let mut finished = look.ui(ui);
+    if look != settings.look {
+        settings.look = look;
+    }
+    ui.separator();
+    ui.heading("Gamepad");
+    let mut gamepad = settings.gamepad.clone();
+    finished |= gamepad.ui(ui);
+    if gamepad != settings.gamepad {
+        settings.gamepad = gamepad;
+    }
+    if finished {
+        settings.save(input_settings_path());
+    }
+    ui.separator();
+    if ui.button("Reset to defaults").clicked() {
+        **settings = InputSettings::default();
+        settings.save(input_settings_path());
+        menu.rebinding = None;
+    }
+}
+
+/// While waiting for a new binding, use the first key, mouse button, scroll or gamepad button the player presses
+fn capture_rebinding_system(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    gamepads: Query<&Gamepad>,
+    mut menu: ResMut<ControlsMenu>,
+    mut settings: ResMut<InputSettings>,
+) {
+    let Some((action, slot)) = menu.rebinding else {
+        mouse_wheel.clear();
+        return;
+    };
+
+    if keyboard.just_pressed(KeyCode::Escape) {
+        menu.rebinding = None;
+        return;
+    }
+
+    let binding = keyboard.get_just_pressed().next().map(|key| Binding::Key(*key))
+        // ignore the click that started the rebinding
+        .or_else(|| mouse.get_just_pressed().next().filter(|_| !mouse.just_released(MouseButton::Left)).map(|button| Binding::Mouse(*button)))
+        .or_else(|| mouse_wheel.read().last().map(|wheel| if wheel.y > 0.0 { Binding::ScrollUp } else { Binding::ScrollDown }))
+        .or_else(|| gamepads.iter().find_map(|gamepad| gamepad.get_just_pressed().next()).map(|button| Binding::Gamepad(*button)));
+    let Some(binding) = binding else { return };
+
+    let conflicts = settings.conflicts(&action, &binding);
+    if !conflicts.is_empty() {
+        warn!("{binding:?} is bound to {action:?} but is also used by {conflicts:?}");
+    }
+
+    let bindings = settings.get_mut(&action);
+    // don't bind the same input twice to one action
+    bindings.retain(|existing| *existing != binding);
+    if slot < bindings.len() {
+        bindings[slot] = binding;
+    } else {
+        bindings.push(binding);
+    }
+    settings.save(input_settings_path());
+    menu.rebinding = None;
+}
+
+/// Apply the new bindings to the player we control and to the HUD, without having to reconnect
+fn apply_input_settings_system(
+    mut commands: Commands,
+    settings: Res<InputSettings>,
+    mut input_maps: Query<&mut InputMap<PlayerInput>, (With<Predicted>, With<Controlled>)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    commands.insert_resource(settings.hud_input_map());
+    for mut input_map in input_maps.iter_mut() {
+        *input_map = settings.input_map();
+    }
+}
@@ -1,10 +1,20 @@
 mod menu;
 mod player;
 mod bot;
+mod chat;
+mod gamepad;
+mod impacts;
+mod input;
+mod scoreboard;
+mod user_config;
 mod weapon;
 
 use bevy::prelude::*;
-use lightyear::prelude::*;
+
+pub use input::InputSettings;
+pub use menu::{CanHost, ConnectionFailed, DiscoveredServer, DiscoveredServers, HostGame, JoinGame, LeaveGame, RefreshServers};
+pub use player::ShipChoice;
+pub use user_config::user_config_path;
 
 pub struct ClientPlugin;
 
@@ -13,16 +23,13 @@ impl Plugin for ClientPlugin {
     fn build(&self, app: &mut App) {
         // PLUGINS
         app.add_plugins(bot::BotPlugin);
+        app.add_plugins(chat::ChatPlugin);
         app.add_plugins(player::PlayerPlugin);
+        app.add_plugins(gamepad::GamepadPlugin);
+        app.add_plugins(impacts::ImpactsPlugin);
+        app.add_plugins(input::InputSettingsPlugin);
+        app.add_plugins(menu::MenuPlugin);
+        app.add_plugins(scoreboard::ScoreboardPlugin);
         app.add_plugins(weapon::WeaponPlugin);
-
-        // SYSTEMS
-        app.add_systems(Startup, connect_client);
     }
 }
-
-fn connect_client(
-    client: Single<Entity, With<Client>>,
-    mut commands: Commands) {
-    commands.entity(client.into_inner()).trigger(Connect);
-}
\ No newline at end of file
@@ -1,336 +1,561 @@
-// use std::net::{IpAddr, Ipv4Addr};
-//
-// use shared::prelude::*;
-// use bevy::{prelude::*, window::CursorGrabMode};
-// use bevy_egui::{
-//     egui::{self, Align2},
-//     EguiContexts,
-// };
-//
-// #[derive(Debug, Clone, Eq, PartialEq)]
-// enum ActiveModal {
-//     ExitGame,
-//     QuitToTitleScreen,
-// }
-//
-// #[derive(Debug, Clone, Eq, PartialEq)]
-// enum ActiveMultiplayerMenu {
-//     Host,
-//     Join,
-// }
-//
-// #[derive(Debug, Clone, Eq, PartialEq)]
-// enum ActiveMenu {
-//     Main,
-//     Multiplayer(ActiveMultiplayerMenu),
-//     Options,
-//     Controls,
-// }
-//
-// impl Default for ActiveMenu {
-//     fn default() -> Self {
-//         ActiveMenu::Main
-//     }
-// }
-//
-// #[derive(Resource)]
-// struct Menu {
-//     active_modal: Option<ActiveModal>,
-//     active_menu: ActiveMenu,
-//     server_port: String,
-//     server_game_mode: GameMode,
-//     server_map: String,
-//     join_port: String,
-//     join_ip: String,
-// }
-//
-// pub struct MenuPlugin;
-// impl Plugin for MenuPlugin {
-//     fn build(&self, app: &mut App) {
-//         app.add_systems(
-//             Update,
-//             (menu_system)
-//                 .chain()
-//                 .run_if(in_state(AppState::TitleScreen).or_else(in_playing_state)),
-//         )
-//             .insert_resource(Menu {
-//                 active_modal: None,
-//                 active_menu: ActiveMenu::Main,
-//                 server_port: DEFAULT_PORT.to_string(),
-//                 server_game_mode: GameMode::Deathmatch,
-//                 server_map: "m4".to_string(),
-//                 join_port: DEFAULT_PORT.to_string(),
-//                 join_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)).to_string(),
-//             });
-//     }
-// }
-//
-// fn menu_system(
-//     world_state: Res<State<WorldState>>,
-//     current_app_state: Res<State<AppState>>,
-//     mut next_app_state: ResMut<NextState<AppState>>,
-//     keyboard_input: Res<ButtonInput<KeyCode>>,
-//     mut ctx: EguiContexts,
-//     mut window: Query<&mut Window>,
-//     mut menu: ResMut<Menu>,
-//     mut app_events: EventWriter<AppEvent>,
-// ) {
-//     let display_menu = match current_app_state.get() {
-//         AppState::TitleScreen => true,
-//         AppState::Playing(PlayingSubState::Menu) => true,
-//         _ => false,
-//     };
-//
-//     let is_title_screen = *current_app_state.get() == AppState::TitleScreen;
-//
-//     if display_menu {
-//         if let Ok(mut window) = window.get_single_mut() {
-//             window.cursor.visible = true;
-//             window.cursor.grab_mode = CursorGrabMode::Confined;
-//         }
-//
-//         if !is_title_screen {
-//             if keyboard_input.just_pressed(KeyCode::Escape) {
-//                 close_menu(
-//                     world_state.get() == &WorldState::SinglePlayer,
-//                     &mut next_app_state,
-//                 );
-//             }
-//         }
-//     } else {
-//         if let Ok(mut window) = window.get_single_mut() {
-//             window.cursor.visible = false;
-//             window.cursor.grab_mode = CursorGrabMode::Locked;
-//         }
-//
-//         if !is_title_screen {
-//             if keyboard_input.just_pressed(KeyCode::Escape) {
-//                 open_menu(
-//                     world_state.get() == &WorldState::SinglePlayer,
-//                     &mut next_app_state,
-//                     &mut menu,
-//                 );
-//             }
-//         }
-//     }
-//
-//     if display_menu {
-//         let window_size = {
-//             let window = ctx.ctx().screen_rect();
-//             (window.width(), window.height())
-//         };
-//
-//         let title = if is_title_screen { "Quantsum" } else { "Menu" };
-//
-//         egui::Window::new(title)
-//             .collapsible(false)
-//             .resizable(false)
-//             .movable(false)
-//             .pivot(Align2::CENTER_CENTER)
-//             .default_pos((window_size.0 / 2.0, window_size.1 / 2.0))
-//             .show(&ctx.ctx_mut(), |ui| {
-//                 ui.horizontal(|ui| {
-//                     if *current_app_state == AppState::TitleScreen {
-//                         if ui.button("Main").clicked() {
-//                             menu.active_menu = ActiveMenu::Main;
-//                         }
-//                     }
-//                     if ui.button("Options").clicked() {
-//                         menu.active_menu = ActiveMenu::Options;
-//                     }
-//                     if ui.button("Controls").clicked() {
-//                         menu.active_menu = ActiveMenu::Controls;
-//                     }
-//                 });
-//
-//                 ui.separator();
-//
-//                 match &menu.active_menu {
-//                     ActiveMenu::Main => {
-//                         if *current_app_state == AppState::TitleScreen {
-//                             ui.heading("Main");
-//                             ui.separator();
-//                             if ui.button("Single Player").clicked() {
-//                                 app_events.send(AppEvent::StartSinglePlayer);
-//                                 close_menu(
-//                                     world_state.get() == &WorldState::SinglePlayer,
-//                                     &mut next_app_state,
-//                                 );
-//                             }
-//                             if ui.button("Multiplayer").clicked() {
-//                                 menu.active_menu =
-//                                     ActiveMenu::Multiplayer(ActiveMultiplayerMenu::Join);
-//                             }
-//                         }
-//                     }
-//                     ActiveMenu::Multiplayer(active_multiplayer_menu) => {
-//                         match active_multiplayer_menu {
-//                             ActiveMultiplayerMenu::Host => {
-//                                 ui.heading("Multiplayer: Host");
-//
-//                                 ui.label("Port");
-//                                 ui.text_edit_singleline(&mut menu.server_port);
-//                                 menu.server_port.retain(|c| c.is_numeric());
-//
-//                                 ui.label("Game Mode");
-//                                 ui.radio_value(
-//                                     &mut menu.server_game_mode,
-//                                     GameMode::Deathmatch,
-//                                     "Deathmatch",
-//                                 );
-//                                 ui.radio_value(&mut menu.server_game_mode, GameMode::Coop, "Coop");
-//
-//                                 ui.label("Map");
-//                                 ui.radio_value(&mut menu.server_map, "m4".to_string(), "Map 4");
-//                                 ui.radio_value(&mut menu.server_map, "dm".to_string(), "Construct");
-//
-//                                 if ui.button("Start Server").clicked() {
-//                                     app_events.send(AppEvent::HostServer(ServerSettings {
-//                                         port: menu.server_port.parse().unwrap_or(DEFAULT_PORT),
-//                                         game_mode: menu.server_game_mode.clone(),
-//                                         map: menu.server_map.clone(),
-//                                     }));
-//                                     close_menu(
-//                                         world_state.get() == &WorldState::SinglePlayer,
-//                                         &mut next_app_state,
-//                                     );
-//                                 }
-//                                 ui.separator();
-//                                 ui.horizontal(|ui| {
-//                                     if ui.button("Join").clicked() {
-//                                         menu.active_menu =
-//                                             ActiveMenu::Multiplayer(ActiveMultiplayerMenu::Join);
-//                                     }
-//                                     if ui.button("Back").clicked() {
-//                                         menu.active_menu = ActiveMenu::Main;
-//                                     }
-//                                 });
-//                             }
-//                             ActiveMultiplayerMenu::Join => {
-//                                 ui.heading("Multiplayer: Join");
-//
-//                                 ui.text_edit_singleline(&mut menu.join_port);
-//                                 menu.join_port.retain(|c| c.is_numeric());
-//
-//                                 ui.text_edit_singleline(&mut menu.join_ip);
-//                                 menu.join_ip.retain(|c| c.is_numeric() || c == '.');
-//
-//                                 if ui.button("Join Server").clicked() {
-//                                     app_events.send(AppEvent::JoinServer(ClientSettings {
-//                                         port: menu.join_port.parse().unwrap_or(DEFAULT_PORT),
-//                                         ip: menu
-//                                             .join_ip
-//                                             .parse()
-//                                             .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST)),
-//                                         player_name: "Player!!!".to_string(),
-//                                     }));
-//                                     close_menu(
-//                                         world_state.get() == &WorldState::SinglePlayer,
-//                                         &mut next_app_state,
-//                                     );
-//                                 }
-//
-//                                 ui.separator();
-//                                 ui.horizontal(|ui| {
-//                                     if ui.button("Host").clicked() {
-//                                         menu.active_menu =
-//                                             ActiveMenu::Multiplayer(ActiveMultiplayerMenu::Host);
-//                                     }
-//                                     if ui.button("Back").clicked() {
-//                                         menu.active_menu = ActiveMenu::Main;
-//                                     }
-//                                 });
-//                             }
-//                         }
-//                     }
-//                     ActiveMenu::Options => {
-//                         ui.heading("Options");
-//                     }
-//                     ActiveMenu::Controls => {
-//                         ui.heading("Controls");
-//                     }
-//                 }
-//
-//                 ui.separator();
-//
-//                 ui.horizontal(|ui| {
-//                     if ui.button("Exit Game").clicked() {
-//                         menu.active_modal = Some(ActiveModal::ExitGame);
-//                     }
-//                     if !is_title_screen {
-//                         if ui.button("Quit").clicked() {
-//                             menu.active_modal = Some(ActiveModal::QuitToTitleScreen);
-//                         }
-//                         if ui.button("Resume").clicked() {
-//                             close_menu(
-//                                 world_state.get() == &WorldState::SinglePlayer,
-//                                 &mut next_app_state,
-//                             );
-//                         }
-//                     }
-//                 });
-//             });
-//
-//         if let Some(active_modal) = &menu.active_modal {
-//             match active_modal {
-//                 ActiveModal::QuitToTitleScreen => {
-//                     egui::Window::new("Quit to Title Screen")
-//                         .collapsible(false)
-//                         .resizable(false)
-//                         .movable(false)
-//                         .pivot(Align2::CENTER_CENTER)
-//                         .default_pos((window_size.0 / 2.0, window_size.1 / 2.0))
-//                         .default_size((200.0, 100.0))
-//                         .show(&ctx.ctx_mut(), |ui| {
-//                             ui.label("Are you sure you want to quit to the title screen?");
-//                             ui.separator();
-//                             ui.horizontal(|ui| {
-//                                 if ui.button("Yes").clicked() {
-//                                     app_events.send(AppEvent::GotoTitleScreen);
-//                                     menu.active_modal = None;
-//                                 }
-//                                 if ui.button("No").clicked() {
-//                                     menu.active_modal = None;
-//                                 }
-//                             });
-//                         });
-//                 }
-//                 ActiveModal::ExitGame => {
-//                     egui::Window::new("Exit Game")
-//                         .collapsible(false)
-//                         .resizable(false)
-//                         .movable(false)
-//                         .pivot(Align2::CENTER_CENTER)
-//                         .default_pos((window_size.0 / 2.0, window_size.1 / 2.0))
-//                         .default_size((200.0, 100.0))
-//                         .show(&ctx.ctx_mut(), |ui| {
-//                             ui.label("Are you sure you want to exit the game?");
-//                             ui.separator();
-//                             ui.horizontal(|ui| {
-//                                 if ui.button("Yes").clicked() {
-//                                     std::process::exit(0);
-//                                 }
-//                                 if ui.button("No").clicked() {
-//                                     menu.active_modal = None;
-//                                 }
-//                             });
-//                         });
-//                 }
-//             }
-//         }
-//     }
-// }
-//
-// fn open_menu(
-//     single_player: bool,
-//     next_app_state: &mut ResMut<NextState<AppState>>,
-//     menu: &mut ResMut<Menu>,
-// ) {
-//     next_app_state.set(AppState::Playing(PlayingSubState::Menu));
-//     menu.active_modal = None;
-//     menu.active_menu = ActiveMenu::Main;
-// }
-//
-// fn close_menu(single_player: bool, next_app_state: &mut ResMut<NextState<AppState>>) {
-//     next_app_state.set(AppState::Playing(PlayingSubState::Playing));
-// }
\ No newline at end of file
+use core::time::Duration;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, CursorOptions, PrimaryWindow};
+use bevy_egui::{
+    egui::{self, Align2},
+    EguiContexts,
+};
+use leafwing_input_manager::prelude::*;
+use lightyear::prelude::*;
+use shared::prelude::*;
+
+use crate::input::{controls_ui, ControlsMenu, InputSettings};
+use crate::player::ShipChoice;
+
+/// Triggered by the menu to host a game on this machine.
+/// The launcher observes it, since it knows how to build the server.
+#[derive(Event, Debug, Clone)]
+pub struct HostGame {
+    pub port: u16,
+    pub game_mode: GameMode,
+}
+
+/// Triggered by the menu to join a server. The launcher observes it and spawns the client.
+#[derive(Event, Debug, Clone)]
+pub struct JoinGame {
+    pub server_addr: SocketAddr,
+}
+
+/// Triggered by the launcher when it could not start connecting to the server we tried to join.
+/// Sends us back to the title screen with the reason.
+#[derive(Event, Debug, Clone)]
+pub struct ConnectionFailed {
+    pub reason: String,
+}
+
+/// Triggered by the menu to leave the current game, the launcher disconnects the client (and stops the server if we are hosting).
+#[derive(Event, Debug, Clone)]
+pub struct LeaveGame;
+
+/// Triggered by the menu to forget the listed LAN servers and search the local network again
+#[derive(Event, Debug, Clone)]
+pub struct RefreshServers;
+
+/// A server that answered the LAN discovery
+#[derive(Debug, Clone)]
+pub struct DiscoveredServer {
+    /// Tells apart the servers that answered from several addresses
+    pub id: u64,
+    /// The address to join the game at
+    pub addr: SocketAddr,
+    pub name: String,
+    pub map: String,
+    pub game_mode: GameMode,
+    pub players: usize,
+    pub ping: Duration,
+}
+
+/// The LAN servers listed in the Join menu, kept up to date by the launcher while we are on the title screen
+#[derive(Resource, Debug, Default)]
+pub struct DiscoveredServers(pub Vec<DiscoveredServer>);
+
+/// Inserted by the launcher when the app can also run a server.
+/// Enables the single player and host buttons.
+#[derive(Resource, Debug, Default)]
+pub struct CanHost;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum ActiveModal {
+    ExitGame,
+    QuitToTitleScreen,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum ActiveMultiplayerMenu {
+    Host,
+    Join,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+enum ActiveMenu {
+    #[default]
+    Main,
+    Multiplayer(ActiveMultiplayerMenu),
+    Options,
+    Controls,
+}
+
+#[derive(Resource)]
+struct Menu {
+    active_modal: Option<ActiveModal>,
+    active_menu: ActiveMenu,
+    server_port: String,
+    server_game_mode: GameMode,
+    join_port: String,
+    join_ip: String,
+    /// Why we got sent back to the title screen, if it wasn't on purpose
+    error: Option<String>,
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Self {
+            active_modal: None,
+            active_menu: ActiveMenu::Main,
+            server_port: DEFAULT_PORT.to_string(),
+            server_game_mode: GameMode::Deathmatch,
+            join_port: DEFAULT_PORT.to_string(),
+            join_ip: IpAddr::V4(Ipv4Addr::LOCALHOST).to_string(),
+            error: None,
+        }
+    }
+}
+
+/// The title screen and the pause menu
+pub(crate) struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_state(AppState::TitleScreen);
+        app.init_resource::<Menu>();
+        app.init_resource::<DiscoveredServers>();
+        app.add_observer(connecting_system);
+        app.add_observer(connected_system);
+        app.add_observer(disconnected_system);
+        app.add_observer(connection_failed_system);
+        app.add_systems(Update, (toggle_pause_menu_system, menu_system).chain());
+        app.add_systems(OnEnter(AppState::TitleScreen), (mouse_pointer_on, despawn_replicated_entities_system));
+        app.add_systems(OnEnter(AppState::Playing(PlayingSubState::Menu)), (mouse_pointer_on, disable_player_input_system));
+        app.add_systems(OnExit(AppState::Playing(PlayingSubState::Menu)), enable_player_input_system);
+        app.add_systems(OnEnter(AppState::Playing(PlayingSubState::Playing)), mouse_pointer_off);
+    }
+}
+
+/// Marks a client that started connecting.
+/// A new client is already `Disconnected`, being disconnected only means the connection failed once it tried.
+#[derive(Component)]
+struct ConnectionAttempted;
+
+fn connecting_system(
+    trigger: Trigger<OnAdd, Connecting>,
+    clients: Query<(), With<Client>>,
+    mut commands: Commands,
+) {
+    if clients.get(trigger.target()).is_ok() {
+        commands.entity(trigger.target()).insert(ConnectionAttempted);
+    }
+}
+
+fn connected_system(
+    trigger: Trigger<OnAdd, Connected>,
+    clients: Query<(), With<Client>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    // the server's links to its clients are also Connected
+    if clients.get(trigger.target()).is_ok() {
+        next_app_state.set(AppState::Playing(PlayingSubState::Playing));
+    }
+}
+
+fn disconnected_system(
+    trigger: Trigger<OnAdd, Disconnected>,
+    clients: Query<Has<ConnectionAttempted>, With<Client>>,
+    current_app_state: Res<State<AppState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut menu: ResMut<Menu>,
+) {
+    let Ok(connection_attempted) = clients.get(trigger.target()) else { return };
+    let error = match current_app_state.get() {
+        AppState::Playing(_) => "Disconnected from the server",
+        // the client is also Disconnected while it's being spawned, before it tries to connect
+        AppState::ConnectingToServer if connection_attempted => "Could not connect to the server",
+        _ => return,
+    };
+    menu.error = Some(error.to_string());
+    next_app_state.set(AppState::TitleScreen);
+}
+
+fn connection_failed_system(
+    trigger: Trigger<ConnectionFailed>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut menu: ResMut<Menu>,
+) {
+    menu.error = Some(trigger.reason.clone());
+    next_app_state.set(AppState::TitleScreen);
+}
+
+/// Remove everything the server replicated to us when we leave a game
+fn despawn_replicated_entities_system(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<Replicated>, With<Predicted>, With<Interpolated>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).try_despawn();
+    }
+}
+
+fn toggle_pause_menu_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    current_app_state: Res<State<AppState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    controls: Res<ControlsMenu>,
+    mut menu: ResMut<Menu>,
+) {
+    // Escape cancels the rebinding instead
+    if !keyboard_input.just_pressed(KeyCode::Escape) || controls.is_rebinding() {
+        return;
+    }
+    match current_app_state.get() {
+        AppState::Playing(PlayingSubState::Playing) => open_menu(&mut next_app_state, &mut menu),
+        AppState::Playing(PlayingSubState::Menu) => close_menu(&mut next_app_state),
+        _ => {}
+    }
+}
+
+fn menu_system(
+    mut commands: Commands,
+    mut ctx: EguiContexts,
+    current_app_state: Res<State<AppState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut menu: ResMut<Menu>,
+    mut controls: ResMut<ControlsMenu>,
+    mut input_settings: ResMut<InputSettings>,
+    mut ship_choice: ResMut<ShipChoice>,
+    ships_data: Option<Res<ShipsData>>,
+    can_host: Option<Res<CanHost>>,
+    discovered_servers: Res<DiscoveredServers>,
+) {
+    let is_title_screen = *current_app_state.get() == AppState::TitleScreen;
+    let window_size = {
+        let window = ctx.ctx_mut().screen_rect();
+        (window.width(), window.height())
+    };
+
+    match current_app_state.get() {
+        AppState::TitleScreen | AppState::Playing(PlayingSubState::Menu) => {}
+        AppState::ConnectingToServer => {
+            egui::Window::new("Connecting")
+                .collapsible(false)
+                .resizable(false)
+                .movable(false)
+                .pivot(Align2::CENTER_CENTER)
+                .default_pos((window_size.0 / 2.0, window_size.1 / 2.0))
+                .show(ctx.ctx_mut(), |ui| {
+                    ui.label("Connecting to the server...");
+                    if ui.button("Cancel").clicked() {
+                        commands.trigger(LeaveGame);
+                        next_app_state.set(AppState::TitleScreen);
+                    }
+                });
+            return;
+        }
+        _ => return,
+    }
+
+    let title = if is_title_screen { "Quantsum" } else { "Menu" };
+
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .movable(false)
+        .pivot(Align2::CENTER_CENTER)
+        .default_pos((window_size.0 / 2.0, window_size.1 / 2.0))
+        .show(ctx.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                if is_title_screen && ui.button("Main").clicked() {
+                    menu.active_menu = ActiveMenu::Main;
+                }
+                if ui.button("Options").clicked() {
+                    menu.active_menu = ActiveMenu::Options;
+                }
+                if ui.button("Controls").clicked() {
+                    menu.active_menu = ActiveMenu::Controls;
+                }
+            });
+
+            ui.separator();
+
+            if let Some(error) = &menu.error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+                ui.separator();
+            }
+
+            match menu.active_menu.clone() {
+                ActiveMenu::Main => {
+                    if is_title_screen {
+                        ui.heading("Main");
+                        ui.separator();
+                        if can_host.is_some() && ui.button("Single Player").clicked() {
+                            commands.trigger(HostGame {
+                                port: DEFAULT_PORT,
+                                game_mode: GameMode::Deathmatch,
+                            });
+                            menu.error = None;
+                            next_app_state.set(AppState::ConnectingToServer);
+                        }
+                        if ui.button("Multiplayer").clicked() {
+                            menu.active_menu =
+                                ActiveMenu::Multiplayer(ActiveMultiplayerMenu::Join);
+                        }
+                    }
+                }
+                ActiveMenu::Multiplayer(active_multiplayer_menu) => {
+                    match active_multiplayer_menu {
+                        ActiveMultiplayerMenu::Host => {
+                            ui.heading("Multiplayer: Host");
+
+                            ui.label("Port");
+                            ui.text_edit_singleline(&mut menu.server_port);
+                            menu.server_port.retain(|c| c.is_numeric());
+
+                            ui.label("Game Mode");
+                            ui.radio_value(&mut menu.server_game_mode, GameMode::Deathmatch, "Deathmatch");
+                            ui.radio_value(&mut menu.server_game_mode, GameMode::TeamDeathmatch, "Team Deathmatch");
+                            ui.radio_value(&mut menu.server_game_mode, GameMode::Coop, "Coop");
+
+                            if ui.button("Start Server").clicked() {
+                                commands.trigger(HostGame {
+                                    port: menu.server_port.parse().unwrap_or(DEFAULT_PORT),
+                                    game_mode: menu.server_game_mode,
+                                });
+                                menu.error = None;
+                                next_app_state.set(AppState::ConnectingToServer);
+                            }
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                if ui.button("Join").clicked() {
+                                    menu.active_menu =
+                                        ActiveMenu::Multiplayer(ActiveMultiplayerMenu::Join);
+                                }
+                                if ui.button("Back").clicked() {
+                                    menu.active_menu = ActiveMenu::Main;
+                                }
+                            });
+                        }
+                        ActiveMultiplayerMenu::Join => {
+                            ui.heading("Multiplayer: Join");
+
+                            ui.horizontal(|ui| {
+                                ui.label("LAN servers");
+                                if ui.button("Refresh").clicked() {
+                                    commands.trigger(RefreshServers);
+                                }
+                            });
+                            if discovered_servers.0.is_empty() {
+                                ui.label("Searching the local network...");
+                            } else {
+                                egui::Grid::new("lan_servers").striped(true).show(ui, |ui| {
+                                    for header in ["Name", "Map", "Mode", "Players", "Ping", ""] {
+                                        ui.strong(header);
+                                    }
+                                    ui.end_row();
+                                    for server in discovered_servers.0.iter() {
+                                        ui.label(server.name.as_str()).on_hover_text(server.addr.to_string());
+                                        ui.label(server.map.as_str());
+                                        ui.label(format!("{:?}", server.game_mode));
+                                        ui.label(server.players.to_string());
+                                        ui.label(format!("{} ms", server.ping.as_millis()));
+                                        if ui.button("Join").clicked() {
+                                            commands.trigger(JoinGame { server_addr: server.addr });
+                                            menu.error = None;
+                                            next_app_state.set(AppState::ConnectingToServer);
+                                        }
+                                        ui.end_row();
+                                    }
+                                });
+                            }
+
+                            ui.separator();
+                            ui.label("Or join by address");
+                            ui.label("IP");
+                            ui.text_edit_singleline(&mut menu.join_ip);
+                            menu.join_ip.retain(|c| c.is_ascii_hexdigit() || c == '.' || c == ':');
+
+                            ui.label("Port");
+                            ui.text_edit_singleline(&mut menu.join_port);
+                            menu.join_port.retain(|c| c.is_numeric());
+
+                            if ui.button("Join Server").clicked() {
+                                match menu.join_ip.parse::<IpAddr>() {
+                                    Ok(ip) => {
+                                        let port = menu.join_port.parse().unwrap_or(DEFAULT_PORT);
+                                        commands.trigger(JoinGame {
+                                            server_addr: SocketAddr::new(ip, port),
+                                        });
+                                        menu.error = None;
+                                        next_app_state.set(AppState::ConnectingToServer);
+                                    }
+                                    Err(_) => {
+                                        menu.error = Some(format!("Invalid IP address: {}", menu.join_ip));
+                                    }
+                                }
+                            }
+
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                if can_host.is_some() && ui.button("Host").clicked() {
+                                    menu.active_menu =
+                                        ActiveMenu::Multiplayer(ActiveMultiplayerMenu::Host);
+                                }
+                                if ui.button("Back").clicked() {
+                                    menu.active_menu = ActiveMenu::Main;
+                                }
+                            });
+                        }
+                    }
+                }
+                ActiveMenu::Options => {
+                    ui.heading("Options");
+                    ui.separator();
+                    ship_options_ui(ui, &mut ship_choice, ships_data.as_deref());
+                }
+                ActiveMenu::Controls => {
+                    ui.heading("Controls");
+                    ui.separator();
+                    controls_ui(ui, &mut controls, &mut input_settings);
+                }
+            }
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Exit Game").clicked() {
+                    menu.active_modal = Some(ActiveModal::ExitGame);
+                }
+                if !is_title_screen {
+                    if ui.button("Quit").clicked() {
+                        menu.active_modal = Some(ActiveModal::QuitToTitleScreen);
+                    }
+                    if ui.button("Resume").clicked() {
+                        close_menu(&mut next_app_state);
+                    }
+                }
+            });
+        });
+
+    if let Some(active_modal) = &menu.active_modal {
+        match active_modal {
+            ActiveModal::QuitToTitleScreen => {
+                egui::Window::new("Quit to Title Screen")
+                    .collapsible(false)
+                    .resizable(false)
+                    .movable(false)
+                    .pivot(Align2::CENTER_CENTER)
+                    .default_pos((window_size.0 / 2.0, window_size.1 / 2.0))
+                    .default_size((200.0, 100.0))
+                    .show(ctx.ctx_mut(), |ui| {
+                        ui.label("Are you sure you want to quit to the title screen?");
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            if ui.button("Yes").clicked() {
+                                commands.trigger(LeaveGame);
+                                next_app_state.set(AppState::TitleScreen);
+                                menu.active_modal = None;
+                                menu.active_menu = ActiveMenu::Main;
+                            }
+                            if ui.button("No").clicked() {
+                                menu.active_modal = None;
+                            }
+                        });
+                    });
+            }
+            ActiveModal::ExitGame => {
+                egui::Window::new("Exit Game")
+                    .collapsible(false)
+                    .resizable(false)
+                    .movable(false)
+                    .pivot(Align2::CENTER_CENTER)
+                    .default_pos((window_size.0 / 2.0, window_size.1 / 2.0))
+                    .default_size((200.0, 100.0))
+                    .show(ctx.ctx_mut(), |ui| {
+                        ui.label("Are you sure you want to exit the game?");
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            if ui.button("Yes").clicked() {
+                                commands.send_event(AppExit::Success);
+                            }
+                            if ui.button("No").clicked() {
+                                menu.active_modal = None;
+                            }
+                        });
+                    });
+            }
+        }
+    }
+}
+
+/// Pick the ship to fly, the server checks that it is allowed in the current game mode
+fn ship_options_ui(ui: &mut egui::Ui, ship_choice: &mut ResMut<ShipChoice>, ships_data: Option<&ShipsData>) {
+    let Some(ships_data) = ships_data else { return };
+    let name = |ship: Option<ShipId>| match ship.and_then(|id| ships_data.ships.get(&id)) {
+        Some(ship) => ship.name.clone(),
+        None => "Default".to_string(),
+    };
+
+    // only touch the resource if the choice changed, so that we don't resend the selection every frame
+    let mut choice = ship_choice.0;
+    let mut ship_ids: Vec<ShipId> = ships_data.ships.keys().copied().collect();
+    ship_ids.sort();
+    ui.horizontal(|ui| {
+        ui.label("Ship");
+        egui::ComboBox::from_id_salt("ship")
+            .selected_text(name(choice))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut choice, None, name(None));
+                for ship_id in ship_ids {
+                    ui.selectable_value(&mut choice, Some(ship_id), name(Some(ship_id)));
+                }
+            });
+    });
+    if choice != ship_choice.0 {
+        ship_choice.0 = choice;
+    }
+}
+
+fn open_menu(
+    next_app_state: &mut ResMut<NextState<AppState>>,
+    menu: &mut ResMut<Menu>,
+) {
+    next_app_state.set(AppState::Playing(PlayingSubState::Menu));
+    menu.active_modal = None;
+    menu.active_menu = ActiveMenu::Options;
+}
+
+fn close_menu(next_app_state: &mut ResMut<NextState<AppState>>) {
+    next_app_state.set(AppState::Playing(PlayingSubState::Playing));
+}
+
+/// The ship keeps flying while the menu is open, but stops listening to the player
+pub(crate) fn disable_player_input_system(
+    mut query: Query<&mut ActionState<PlayerInput>, With<InputMap<PlayerInput>>>,
+    mut controls: ResMut<ControlsMenu>,
+) {
+    controls.cancel();
+    for mut action_state in query.iter_mut() {
+        action_state.disable();
+    }
+}
+
+pub(crate) fn enable_player_input_system(
+    mut query: Query<&mut ActionState<PlayerInput>, With<InputMap<PlayerInput>>>,
+    mut controls: ResMut<ControlsMenu>,
+) {
+    controls.cancel();
+    for mut action_state in query.iter_mut() {
+        action_state.enable();
+    }
+}
+
+fn mouse_pointer_on(mut window: Single<&mut Window, With<PrimaryWindow>>) {
+    window.cursor_options = CursorOptions::default();
+}
+
+fn mouse_pointer_off(mut window: Single<&mut Window, With<PrimaryWindow>>) {
+    window.cursor_options = CursorOptions {
+        visible: false,
+        grab_mode: CursorGrabMode::Confined, // DO NOT USE LOCKED! For some reason it causes jittering. Confined is fine.
+        ..default()
+    };
+}
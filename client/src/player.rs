@@ -2,9 +2,10 @@ use avian3d::prelude::*;
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 use lightyear::prelude::{client::*, *};
+use crate::input::InputSettings;
 use shared::player::PlayerShip;
-use shared::prelude::{PlayerInput};
-use shared::ships::get_shared_ship_components;
+use shared::prelude::{PlayerInput, ShipSelectionChannel};
+use shared::ships::{get_shared_ship_components, Ship, ShipColliders, ShipId, ShipSelection, ShipsData};
 
 pub(crate) struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
@@ -25,7 +26,31 @@ impl Plugin for PlayerPlugin {
         // make sure that client cannot apply inputs before the connection is synced
         // we add the system in Last so that on the first time the InputMap is spawned, we don't immediately
         // send an InputMessage to the server
-        app.add_systems(Last, handle_predicted_spawn);
+        app.add_systems(Last, handle_predicted_spawn.run_if(resource_exists::<ShipsData>));
+
+        app.init_resource::<ShipChoice>();
+        app.add_systems(Update, send_ship_selection);
+    }
+}
+
+/// The ship the player wants to fly.
+/// If `None`, the server gives us its default ship.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct ShipChoice(pub Option<ShipId>);
+
+/// Tell the server which ship we want to fly, when we connect and whenever the choice changes.
+/// The server uses it for our next spawn.
+fn send_ship_selection(
+    ship_choice: Res<ShipChoice>,
+    client: Single<(&mut MessageSender<ShipSelection>, Ref<Connected>), With<Client>>,
+) {
+    let (mut sender, connected) = client.into_inner();
+    if !ship_choice.is_changed() && !connected.is_added() {
+        return;
+    }
+    if let Some(ship_id) = ship_choice.0 {
+        info!("Requesting ship {ship_id}");
+        sender.send::<ShipSelectionChannel>(ShipSelection { ship_id });
     }
 }
 
@@ -35,35 +60,24 @@ impl Plugin for PlayerPlugin {
 fn handle_predicted_spawn(
     mut commands: Commands,
     _: Single<(), (With<Client>, With<IsSynced<InputTimeline>>)>,
-    predicted_player: Query<Entity, (With<Controlled>, With<PlayerShip>, With<Predicted>, Without<InputMap<PlayerInput>>)>
+    ships_data: Res<ShipsData>,
+    ship_colliders: Res<ShipColliders>,
+    input_settings: Res<InputSettings>,
+    predicted_player: Query<(Entity, &Ship), (With<Controlled>, With<PlayerShip>, With<Predicted>, Without<InputMap<PlayerInput>>)>
 ) {
-    for entity in predicted_player.iter() {
-        let input_map = InputMap::<PlayerInput>::default()
-            .with_multiple([
-                (PlayerInput::MoveForward, KeyCode::KeyW),
-                (PlayerInput::MoveBackward, KeyCode::KeyS),
-                (PlayerInput::MoveLeft, KeyCode::KeyA),
-                (PlayerInput::MoveRight, KeyCode::KeyD),
-                (PlayerInput::MoveUp, KeyCode::Space),
-                (PlayerInput::MoveDown, KeyCode::ShiftLeft),
-                (PlayerInput::RollLeft, KeyCode::KeyQ),
-                (PlayerInput::RollRight, KeyCode::KeyE),
-                (PlayerInput::Weapon1, KeyCode::Digit1),
-                (PlayerInput::Weapon2, KeyCode::Digit2),
-                (PlayerInput::Weapon3, KeyCode::Digit3),
-                (PlayerInput::Weapon4, KeyCode::Digit4),
-                (PlayerInput::Weapon5, KeyCode::Digit5),
-                (PlayerInput::ToggleMousePointer, KeyCode::Tab),
-            ])
-            .with(PlayerInput::NextWeapon, MouseScrollDirection::UP)
-            .with(PlayerInput::PreviousWeapon, MouseScrollDirection::DOWN)
-            .with(PlayerInput::ShootPrimary, MouseButton::Left)
-            .with(PlayerInput::AfterBurners, MouseButton::Right)
-            .with_dual_axis(PlayerInput::Look, MouseMove::default());
+    for (entity, ship) in predicted_player.iter() {
+        let Some(ship_data) = ships_data.ships.get(&ship.0) else {
+            error!("Ship data not found for ship id: {}", ship.0);
+            continue;
+        };
+        // wait until the collider is ready so that we simulate the same physics as the server
+        let Some(collider) = ship_colliders.get(ship.0) else { continue };
+
+        let input_map = input_settings.input_map();
 
         // Adds an InputMap to Predicted so that the user can control the predicted entity
         commands.entity(entity).insert((input_map,
-            get_shared_ship_components(Collider::sphere(0.5))
+            get_shared_ship_components(collider, &ship_data.physics)
         ));
     }
 }
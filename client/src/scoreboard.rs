@@ -0,0 +1,22 @@
+use bevy::prelude::*;
+use lightyear::connection::host::HostClient;
+use lightyear::prelude::*;
+use shared::prelude::KillFeedEntry;
+
+/// The scoreboard itself is replicated, only the kill feed needs to be received
+pub(crate) struct ScoreboardPlugin;
+
+impl Plugin for ScoreboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, receive_kill_feed.after(MessageSet::Receive));
+    }
+}
+
+/// Trigger the kills the server tells us about so that the HUD can show them
+fn receive_kill_feed(
+    mut commands: Commands,
+    // the host-client already sees the entries triggered by the server
+    mut receiver: Single<&mut MessageReceiver<KillFeedEntry>, (With<Client>, With<Connected>, Without<HostClient>)>,
+) {
+    receiver.receive().for_each(|entry| commands.trigger(entry));
+}
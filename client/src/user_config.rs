@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+/// The folder of the game in the per-user config directory
+const APP_DIR: &str = "sixdof";
+
+/// Where a file of the local player is stored, so that it is found wherever the game is launched from:
+/// `$XDG_CONFIG_HOME/sixdof` (or `~/.config/sixdof`) on Linux, `~/Library/Application Support/sixdof` on macOS
+/// and `%APPDATA%\sixdof` on Windows. The directory is created if needed.
+/// Falls back to the working directory if there is no config directory.
+pub fn user_config_path(file_name: &str) -> PathBuf {
+    let Some(dir) = config_dir().map(|dir| dir.join(APP_DIR)) else {
+        return PathBuf::from(file_name);
+    };
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        warn!("Failed to create the config directory {}, using the working directory instead: {}", dir.display(), e);
+        return PathBuf::from(file_name);
+    }
+    dir.join(file_name)
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let env_dir = |name: &str| std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+    }
+}
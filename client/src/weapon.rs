@@ -5,7 +5,7 @@ use lightyear::connection::host::HostClient;
 use lightyear::prelude::*;
 use lightyear::utils::ready_buffer::ReadyBuffer;
 use shared::{prelude::{CurrentWeaponIndex, GameLayer, PlayerInput, UniqueIdentity}, weapons::{handle_shooting, Projectile, WeaponFiredEvent, WeaponInventory, WeaponsData}};
-use shared::prelude::{ProjectileInfo, Ship, WeaponsSet};
+use shared::prelude::{HitConfirmed, ProjectileInfo, Ship, WeaponsSet};
 
 pub(crate) struct WeaponPlugin;
 
@@ -16,8 +16,10 @@ impl Plugin for WeaponPlugin {
         app.add_systems(FixedUpdate, predicted_shoot_system
             .in_set(WeaponsSet::Shoot));
 
-        app.add_systems(PreUpdate, buffer_fire_weapon_event
-            .after(MessageSet::Receive));
+        app.add_systems(PreUpdate, (
+            buffer_fire_weapon_event,
+            receive_hit_confirmations,
+        ).after(MessageSet::Receive));
         app.add_systems(PostUpdate, shoot_interpolated_bullets
             .in_set(WeaponsSet::Shoot)
             // the interpolation time is updated in the lightyear SyncSet
@@ -152,3 +154,12 @@ fn projectile_predict_hit_detection_system(
         }
     }
 }
+
+/// Trigger the hits the server confirmed, so that the HUD can show hit markers
+fn receive_hit_confirmations(
+    mut commands: Commands,
+    // the host-client already sees the event triggered by the server
+    mut receiver: Single<&mut MessageReceiver<HitConfirmed>, (With<Client>, With<Connected>, Without<HostClient>)>,
+) {
+    receiver.receive().for_each(|hit| commands.trigger(hit));
+}
@@ -1,16 +1,15 @@
-use core::time::Duration;
 use crate::settings;
 use bevy::asset::AssetPlugin;
 use bevy::image::ImageSamplerDescriptor;
 use bevy::prelude::*;
 use bevy_trenchbroom::util::ImageSamplerRepeatExt;
 use lightyear::prelude::client::ClientPlugins;
-use crate::settings::TICK_RATE;
+use crate::settings::NetworkSettings;
 
 pub struct ClientApp(App);
 
 impl ClientApp {
-    pub fn new(client_id: u64) -> Self {
+    pub fn new(client_id: u64, ship: Option<u32>, network: NetworkSettings) -> Self {
         let mut app = App::new();
         app.add_plugins(
             DefaultPlugins
@@ -29,14 +28,14 @@ impl ClientApp {
                     default_sampler: ImageSamplerDescriptor::default().repeat(),
                 })
         );
-        let tick_duration =  Duration::from_secs_f64(1.0 / TICK_RATE);
-        app.add_plugins(ClientPlugins { tick_duration });
+        app.add_plugins(ClientPlugins { tick_duration: network.tick_duration() });
         app.add_plugins(shared::SharedPlugin { headless: false });
         app.add_plugins(client::ClientPlugin);
+        app.insert_resource(client::ShipChoice(ship));
         app.add_plugins(renderer::RendererPlugin);
-
-        // spawn client
-        app.world_mut().spawn(settings::client(client_id));
+        // the client is spawned when the player joins a server from the menu
+        app.add_plugins(crate::connection::ConnectionPlugin { client_id, can_host: false });
+        app.insert_resource(network);
 
         Self(app)
     }
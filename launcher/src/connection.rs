@@ -0,0 +1,165 @@
+//! Starts and leaves games when the player picks them in the menu
+use std::net::SocketAddr;
+
+use bevy::prelude::*;
+use client::{ConnectionFailed, JoinGame, LeaveGame};
+use crossbeam_channel::{Receiver, TryRecvError};
+use lightyear::netcode::ConnectToken;
+use lightyear::prelude::*;
+#[cfg(feature = "server")]
+use lightyear::prelude::server::*;
+#[cfg(feature = "server")]
+use shared::prelude::GameMode;
+use shared::prelude::AppState;
+use crate::settings::{self, NetworkSettings};
+use crate::token;
+
+/// Observes the [`client::HostGame`], [`JoinGame`] and [`LeaveGame`] events triggered by the menu
+pub(crate) struct ConnectionPlugin {
+    pub(crate) client_id: u64,
+    /// Whether this app also runs the server plugins, and so can host games
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub(crate) can_host: bool,
+}
+
+/// The id this client uses when connecting to a server
+#[derive(Resource, Debug, Clone, Copy)]
+struct LocalClientId(u64);
+
+/// A join in progress, waiting for the token service of the server to answer
+#[derive(Resource)]
+struct PendingJoin {
+    server_addr: SocketAddr,
+    token: Receiver<Result<ConnectToken, String>>,
+}
+
+impl Plugin for ConnectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LocalClientId(self.client_id));
+        app.add_plugins(crate::discovery::ServerBrowserPlugin);
+        app.add_observer(join_game);
+        app.add_observer(leave_game);
+        app.add_systems(Startup, auto_join_system);
+        app.add_systems(Update, receive_connect_token_system.run_if(resource_exists::<PendingJoin>));
+        #[cfg(feature = "server")]
+        if self.can_host {
+            app.insert_resource(client::CanHost);
+            app.add_observer(host_game);
+        }
+    }
+}
+
+/// Remove the client and server of the previous game
+fn despawn_previous_game(
+    commands: &mut Commands,
+    previous: impl Iterator<Item = Entity>,
+) {
+    for entity in previous {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Skip the title screen if a server was given with `--connect`
+fn auto_join_system(
+    mut commands: Commands,
+    network: Res<NetworkSettings>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if let Some(server_addr) = network.connect_addr {
+        commands.trigger(JoinGame { server_addr });
+        next_app_state.set(AppState::ConnectingToServer);
+    }
+}
+
+fn join_game(
+    trigger: Trigger<JoinGame>,
+    mut commands: Commands,
+    client_id: Res<LocalClientId>,
+    clients: Query<Entity, With<Client>>,
+    #[cfg(feature = "server")]
+    servers: Query<Entity, With<Server>>,
+) {
+    despawn_previous_game(&mut commands, clients.iter());
+    #[cfg(feature = "server")]
+    despawn_previous_game(&mut commands, servers.iter());
+
+    // the token service can be slow to answer, don't freeze the menu while we wait
+    let server_addr = trigger.server_addr;
+    let client_id = client_id.0;
+    info!("Requesting a connect token from the server at {server_addr}");
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    std::thread::spawn(move || {
+        let _ = sender.send(token::request_connect_token(server_addr, client_id));
+    });
+    commands.insert_resource(PendingJoin { server_addr, token: receiver });
+}
+
+fn receive_connect_token_system(
+    mut commands: Commands,
+    pending: Res<PendingJoin>,
+    network: Res<NetworkSettings>,
+) {
+    let result = match pending.token.try_recv() {
+        Ok(result) => result,
+        Err(TryRecvError::Empty) => return,
+        Err(TryRecvError::Disconnected) => Err("The connect token request was interrupted".to_string()),
+    };
+    commands.remove_resource::<PendingJoin>();
+    match result {
+        Ok(token) => {
+            info!("Joining server at {}", pending.server_addr);
+            let client = commands.spawn(settings::client(token, pending.server_addr, &network)).id();
+            commands.entity(client).trigger(Connect);
+        }
+        Err(reason) => {
+            error!("Failed to join the server at {}: {reason}", pending.server_addr);
+            commands.trigger(ConnectionFailed { reason });
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+fn host_game(
+    trigger: Trigger<client::HostGame>,
+    mut commands: Commands,
+    mut game_mode: ResMut<GameMode>,
+    network: Res<NetworkSettings>,
+    clients: Query<Entity, With<Client>>,
+    servers: Query<Entity, With<Server>>,
+) {
+    despawn_previous_game(&mut commands, clients.iter());
+    despawn_previous_game(&mut commands, servers.iter());
+    commands.remove_resource::<PendingJoin>();
+
+    info!("Hosting a {:?} game on port {}", trigger.game_mode, trigger.port);
+    *game_mode = trigger.game_mode;
+    // keep the interface from `--bind`, but use the port picked in the menu
+    let bind_addr = SocketAddr::new(network.bind_addr.ip(), trigger.port);
+    let server = commands.spawn(settings::server(bind_addr, &network)).id();
+    commands.entity(server).trigger(Start);
+    // the local player connects to the server inside the same app
+    let client = commands.spawn((
+        Client::default(),
+        Name::new("HostClient"),
+        LinkOf { server },
+    )).id();
+    commands.entity(client).trigger(Connect);
+}
+
+fn leave_game(
+    _: Trigger<LeaveGame>,
+    mut commands: Commands,
+    clients: Query<Entity, With<Client>>,
+    #[cfg(feature = "server")]
+    servers: Query<Entity, With<Server>>,
+) {
+    // stop waiting for the token if we were still joining
+    commands.remove_resource::<PendingJoin>();
+    for client in clients.iter() {
+        commands.entity(client).trigger(Disconnect);
+    }
+    #[cfg(feature = "server")]
+    for server in servers.iter() {
+        commands.entity(server).trigger(Stop);
+    }
+}
@@ -0,0 +1,87 @@
+/// Lets you manage the server at runtime by typing commands in the terminal running it
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, TryRecvError};
+use server::BotSettings;
+use shared::bot::BotDifficulty;
+
+pub(crate) struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        // stdin is blocking, so we read it on a separate thread and forward the lines to the app
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        app.insert_resource(ConsoleInput(receiver));
+        app.add_systems(Update, console_system);
+    }
+}
+
+#[derive(Resource)]
+struct ConsoleInput(Receiver<String>);
+
+const HELP: &str = "Available commands:
+    bots add [count]                    add bots
+    bots remove [count]                 remove bots
+    bots fill <slots|off>               fill empty player slots with bots
+    bots difficulty <easy|normal|hard>  set the difficulty of newly spawned bots
+    bots shoot <on|off>                 let the bots shoot at their targets or only chase them";
+
+fn console_system(
+    input: Res<ConsoleInput>,
+    mut bot_settings: ResMut<BotSettings>,
+) {
+    loop {
+        let line = match input.0.try_recv() {
+            Ok(line) => line,
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return,
+        };
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        match args.as_slice() {
+            [] => {}
+            ["bots", "add", rest @ ..] => {
+                let count = rest.first().and_then(|c| c.parse().ok()).unwrap_or(1);
+                bot_settings.bot_count += count;
+                info!("Bot count set to {}", bot_settings.bot_count);
+            }
+            ["bots", "remove", rest @ ..] => {
+                let count = rest.first().and_then(|c| c.parse().ok()).unwrap_or(1);
+                bot_settings.bot_count = bot_settings.bot_count.saturating_sub(count);
+                info!("Bot count set to {}", bot_settings.bot_count);
+            }
+            ["bots", "fill", "off"] => {
+                bot_settings.fill_player_slots = None;
+                info!("No longer filling player slots with bots");
+            }
+            ["bots", "fill", slots] => match slots.parse() {
+                Ok(slots) => {
+                    bot_settings.fill_player_slots = Some(slots);
+                    info!("Filling up to {} player slots with bots", slots);
+                }
+                Err(_) => warn!("Invalid number of slots: {}", slots),
+            },
+            ["bots", "difficulty", difficulty] => match difficulty.parse::<BotDifficulty>() {
+                Ok(difficulty) => {
+                    bot_settings.difficulty = difficulty;
+                    info!("Newly spawned bots will be {:?}", difficulty);
+                }
+                Err(e) => warn!("{}", e),
+            },
+            ["bots", "shoot", "on"] => {
+                bot_settings.shooting = true;
+                info!("Bots shoot at their targets");
+            }
+            ["bots", "shoot", "off"] => {
+                bot_settings.shooting = false;
+                info!("Bots only chase their targets");
+            }
+            _ => info!("Unknown command: {}\n{}", line, HELP),
+        }
+    }
+}
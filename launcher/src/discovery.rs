@@ -0,0 +1,308 @@
+//! LAN discovery: clients broadcast a query on the local network, and every started server answers it
+//! with its name, map, mode and number of players, so that the menu can list them.
+//!
+//! Queries and answers are small RON messages sent over UDP to [`DISCOVERY_PORT`].
+use std::io;
+use std::net::UdpSocket;
+
+use serde::{Deserialize, Serialize};
+use shared::prelude::GameMode;
+
+
+/// The port servers listen on for discovery queries, it is the same for every server.
+/// Servers share it, so that several servers on the same machine all get the broadcast queries.
+const DISCOVERY_PORT: u16 = 5000;
+/// Both messages are well below this size
+const MAX_MESSAGE_BYTES: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct DiscoveryQuery {
+    protocol_id: u64,
+    /// Echoed back by the server, to match the answer with the query and measure the ping
+    nonce: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct DiscoveryAnswer {
+    protocol_id: u64,
+    nonce: u64,
+    /// Random id of the server, the same server can answer from several addresses
+    server_id: u64,
+    name: String,
+    map: String,
+    game_mode: GameMode,
+    players: usize,
+    /// The port to join the game on, the address is the one the answer came from
+    game_port: u16,
+}
+
+/// Read the next message waiting on a non blocking socket. Returns `None` once there are none left.
+/// Messages that are not ours are skipped.
+fn receive<T: for<'de> Deserialize<'de>>(socket: &UdpSocket) -> Option<(T, std::net::SocketAddr)> {
+    let mut buffer = [0; MAX_MESSAGE_BYTES];
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((len, from)) => {
+                let Ok(text) = std::str::from_utf8(&buffer[..len]) else { continue };
+                if let Ok(message) = ron::from_str(text) {
+                    return Some((message, from));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return None,
+            // on some platforms an unreachable peer shows up as an error on the next read
+            Err(_) => continue,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+pub(crate) use responder::DiscoveryResponderPlugin;
+
+#[cfg(feature = "server")]
+mod responder {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use bevy::prelude::*;
+    use socket2::{Domain, Protocol, Socket, Type};
+    use lightyear::prelude::*;
+    use lightyear::prelude::server::*;
+    use shared::prelude::CurrentMap;
+
+    use super::*;
+    use crate::settings::NetworkSettings;
+
+    /// Answers the LAN discovery queries while the server is started
+    pub(crate) struct DiscoveryResponderPlugin;
+
+    impl Plugin for DiscoveryResponderPlugin {
+        fn build(&self, app: &mut App) {
+            app.add_observer(start_discovery_responder);
+            app.add_observer(stop_discovery_responder);
+            app.add_systems(Update, answer_discovery_queries_system.run_if(any_with_component::<DiscoveryResponder>));
+        }
+    }
+
+    /// Lives on the server entity while it answers discovery queries
+    #[derive(Component)]
+    struct DiscoveryResponder {
+        socket: UdpSocket,
+        server_id: u64,
+    }
+
+    fn start_discovery_responder(
+        trigger: Trigger<OnAdd, Started>,
+        servers: Query<(), With<Server>>,
+        mut commands: Commands,
+    ) {
+        if servers.get(trigger.target()).is_err() {
+            return;
+        }
+        match bind_shared_socket() {
+            Ok(socket) => {
+                info!("Answering LAN discovery queries on port {DISCOVERY_PORT}");
+                commands.entity(trigger.target()).insert(DiscoveryResponder {
+                    socket,
+                    server_id: rand::random(),
+                });
+            }
+            Err(e) => warn!("The server will not show up in the LAN server browser, failed to bind port {DISCOVERY_PORT}: {e}"),
+        }
+    }
+
+    /// Bind the discovery port, even if other servers on this machine already did
+    fn bind_shared_socket() -> io::Result<UdpSocket> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(true)?;
+        socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT)).into())?;
+        socket.set_nonblocking(true)?;
+        Ok(socket.into())
+    }
+
+    fn stop_discovery_responder(
+        trigger: Trigger<OnAdd, Stopped>,
+        mut commands: Commands,
+    ) {
+        commands.entity(trigger.target()).remove::<DiscoveryResponder>();
+    }
+
+    fn answer_discovery_queries_system(
+        responders: Query<(&DiscoveryResponder, &LocalAddr)>,
+        clients: Query<(), (With<ClientOf>, With<Connected>)>,
+        game_mode: Res<GameMode>,
+        current_map: Query<&CurrentMap>,
+        network: Res<NetworkSettings>,
+    ) {
+        for (responder, local_addr) in responders.iter() {
+            while let Some((query, from)) = receive::<DiscoveryQuery>(&responder.socket) {
+                if query.protocol_id != network.protocol_id {
+                    continue;
+                }
+                let answer = DiscoveryAnswer {
+                    protocol_id: network.protocol_id,
+                    nonce: query.nonce,
+                    server_id: responder.server_id,
+                    name: network.server_name.clone(),
+                    map: current_map.single().map(|map| map.0.clone()).unwrap_or_default(),
+                    game_mode: *game_mode,
+                    players: clients.iter().count(),
+                    game_port: local_addr.0.port(),
+                };
+                let Ok(answer) = ron::to_string(&answer) else { continue };
+                if let Err(e) = responder.socket.send_to(answer.as_bytes(), from) {
+                    debug!("Failed to answer the discovery query from {from}: {e}");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+pub(crate) use browser::ServerBrowserPlugin;
+
+#[cfg(feature = "client")]
+mod browser {
+    use core::time::Duration;
+    use std::collections::HashMap;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::Instant;
+
+    use bevy::prelude::*;
+    use client::{DiscoveredServer, DiscoveredServers, RefreshServers};
+    use shared::prelude::AppState;
+
+    use super::*;
+    use crate::settings::NetworkSettings;
+
+    /// How often the client searches for servers while on the title screen
+    const QUERY_INTERVAL: Duration = Duration::from_secs(2);
+    /// Servers that stop answering are removed from the list after this long
+    const SERVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Keeps [`DiscoveredServers`] up to date while we are on the title screen
+    pub(crate) struct ServerBrowserPlugin;
+
+    impl Plugin for ServerBrowserPlugin {
+        fn build(&self, app: &mut App) {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).and_then(|socket| {
+                socket.set_broadcast(true)?;
+                socket.set_nonblocking(true)?;
+                Ok(socket)
+            });
+            let socket = match socket {
+                Ok(socket) => socket,
+                Err(e) => {
+                    warn!("LAN servers will not be listed, failed to open the discovery socket: {e}");
+                    return;
+                }
+            };
+            app.insert_resource(ServerBrowser {
+                socket,
+                queries: default(),
+                last_seen: default(),
+                timer: Timer::new(QUERY_INTERVAL, TimerMode::Repeating),
+            });
+            app.add_observer(refresh_servers);
+            app.add_systems(OnEnter(AppState::TitleScreen), start_browsing_system);
+            app.add_systems(Update, (
+                send_discovery_query_system,
+                receive_discovery_answers_system,
+            ).chain().run_if(in_state(AppState::TitleScreen)));
+        }
+    }
+
+    #[derive(Resource)]
+    struct ServerBrowser {
+        socket: UdpSocket,
+        /// When the queries that may still be answered were sent
+        queries: HashMap<u64, Instant>,
+        /// When each listed server last answered
+        last_seen: HashMap<u64, Instant>,
+        timer: Timer,
+    }
+
+    impl ServerBrowser {
+        fn send_query(&mut self, protocol_id: u64) {
+            let nonce = rand::random();
+            let Ok(query) = ron::to_string(&DiscoveryQuery { protocol_id, nonce }) else { return };
+            // the broadcast doesn't always loop back, so also ask the servers running on this machine
+            for target in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+                if let Err(e) = self.socket.send_to(query.as_bytes(), (target, DISCOVERY_PORT)) {
+                    debug!("Failed to send the discovery query to {target}: {e}");
+                }
+            }
+            self.queries.insert(nonce, Instant::now());
+        }
+
+        fn clear(&mut self, servers: &mut DiscoveredServers) {
+            servers.0.clear();
+            self.last_seen.clear();
+            self.queries.clear();
+            self.timer.reset();
+        }
+    }
+
+    fn start_browsing_system(
+        network: Res<NetworkSettings>,
+        mut browser: ResMut<ServerBrowser>,
+        mut servers: ResMut<DiscoveredServers>,
+    ) {
+        browser.clear(&mut servers);
+        browser.send_query(network.protocol_id);
+    }
+
+    fn refresh_servers(
+        _: Trigger<RefreshServers>,
+        network: Res<NetworkSettings>,
+        mut browser: ResMut<ServerBrowser>,
+        mut servers: ResMut<DiscoveredServers>,
+    ) {
+        browser.clear(&mut servers);
+        browser.send_query(network.protocol_id);
+    }
+
+    fn send_discovery_query_system(
+        time: Res<Time>,
+        network: Res<NetworkSettings>,
+        mut browser: ResMut<ServerBrowser>,
+        mut servers: ResMut<DiscoveredServers>,
+    ) {
+        if !browser.timer.tick(time.delta()).just_finished() {
+            return;
+        }
+        let browser = &mut *browser;
+        browser.queries.retain(|_, sent| sent.elapsed() < SERVER_TIMEOUT);
+        browser.last_seen.retain(|_, seen| seen.elapsed() < SERVER_TIMEOUT);
+        servers.0.retain(|server| browser.last_seen.contains_key(&server.id));
+        browser.send_query(network.protocol_id);
+    }
+
+    fn receive_discovery_answers_system(
+        network: Res<NetworkSettings>,
+        mut browser: ResMut<ServerBrowser>,
+        mut servers: ResMut<DiscoveredServers>,
+    ) {
+        while let Some((answer, from)) = receive::<DiscoveryAnswer>(&browser.socket) {
+            if answer.protocol_id != network.protocol_id {
+                continue;
+            }
+            let Some(sent) = browser.queries.get(&answer.nonce) else { continue };
+            let server = DiscoveredServer {
+                id: answer.server_id,
+                addr: SocketAddr::new(from.ip(), answer.game_port),
+                name: answer.name,
+                map: answer.map,
+                game_mode: answer.game_mode,
+                players: answer.players,
+                ping: sent.elapsed(),
+            };
+            browser.last_seen.insert(server.id, Instant::now());
+            match servers.0.iter_mut().find(|listed| listed.id == server.id) {
+                // keep the address we first heard from, the other ones are the same server
+                Some(listed) => *listed = DiscoveredServer { addr: listed.addr, ..server },
+                None => servers.0.push(server),
+            }
+        }
+    }
+}
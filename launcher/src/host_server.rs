@@ -1,14 +1,13 @@
-use std::time::Duration;
 use crate::settings;
 use bevy::asset::AssetPlugin;
 use bevy::prelude::*;
 use bevy::DefaultPlugins;
-use lightyear::prelude::{client::ClientPlugins, server::ServerPlugins, Client, LinkOf};
-use crate::settings::TICK_RATE;
+use lightyear::prelude::{client::ClientPlugins, server::ServerPlugins};
+use crate::settings::NetworkSettings;
 
 pub struct HostServer(App);
 impl HostServer {
-    pub fn new(client_id: u64) -> Self {
+    pub fn new(client_id: u64, ship: Option<u32>, bot_settings: server::BotSettings, network: NetworkSettings) -> Self {
         // gui app
         let mut app = App::new();
         app.add_plugins(
@@ -24,22 +23,23 @@ impl HostServer {
                 .set(settings::log_plugin())
                 .set(settings::window_plugin()),
         );
-        let tick_duration =  Duration::from_secs_f64(1.0 / TICK_RATE);
+        let tick_duration = network.tick_duration();
         app.add_plugins(ClientPlugins { tick_duration });
         app.add_plugins(ServerPlugins { tick_duration });
         app.add_plugins(shared::SharedPlugin { headless: false });
         app.add_plugins(client::ClientPlugin);
+        app.insert_resource(client::ShipChoice(ship));
         app.add_plugins(server::ServerPlugin);
+        app.add_observer(settings::replication_sender(network.replication_interval));
+        app.insert_resource(bot_settings);
+        app.insert_resource(server::MapRotation::new(network.map_rotation.clone()));
+        app.add_plugins(crate::console::ConsolePlugin);
+        app.add_plugins(crate::token::TokenServicePlugin);
+        app.add_plugins(crate::discovery::DiscoveryResponderPlugin);
         app.add_plugins(renderer::RendererPlugin);
-
-        // spawn server
-        let server = app.world_mut().spawn(settings::server()).id();
-        // spawn host client
-        app.world_mut().spawn((
-            Client::default(),
-            Name::new("HostClient"),
-            LinkOf { server },
-        ));
+        // the server and the host client are spawned when the player hosts a game from the menu
+        app.add_plugins(crate::connection::ConnectionPlugin { client_id, can_host: true });
+        app.insert_resource(network);
         Self(app)
     }
 
@@ -1,16 +1,27 @@
 #[cfg(feature = "client")]
 mod client;
+#[cfg(feature = "client")]
+mod connection;
 #[cfg(all(feature = "client", feature = "server"))]
 mod host_server;
 #[cfg(all(feature = "client", feature = "server"))]
 mod separate;
 #[cfg(feature = "server")]
 mod server;
+#[cfg(feature = "server")]
+mod console;
 /// Provides a CLI to start the app in different modes
 pub(crate) mod settings;
+mod token;
+mod discovery;
 
 use bevy::prelude::*;
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap::error::ErrorKind;
+#[cfg(feature = "server")]
+use shared::bot::BotDifficulty;
+#[cfg(feature = "server")]
+use shared::prelude::GameMode;
 
 /// CLI options to create an [`App`]
 #[derive(Parser, Debug)]
@@ -18,6 +29,8 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub mode: Option<Mode>,
+    #[command(flatten)]
+    pub network: settings::NetworkArgs,
 }
 
 #[derive(Subcommand, Debug)]
@@ -27,16 +40,30 @@ pub enum Mode {
     Client {
         #[arg(short, long, default_value = None)]
         client_id: Option<u64>,
+        /// The id of the ship to fly (see data/ships.ron). The server picks one if not set
+        #[arg(long, default_value = None)]
+        ship: Option<u32>,
     },
     #[cfg(feature = "server")]
     /// Runs the app in server mode
-    Server,
+    Server {
+        /// The game mode of the matches: dm, tdm or coop
+        #[arg(long, default_value = "dm")]
+        game_mode: GameMode,
+        #[command(flatten)]
+        bots: BotArgs,
+    },
     #[cfg(all(feature = "client", feature = "server"))]
-    /// Creates two bevy apps: a client app and a server app.
-    /// Data gets passed between the two via channels.
+    /// Creates two bevy apps: a client app and a server app, each running on its own thread.
+    /// Data gets passed between the two via channels, through the link conditioner.
     Separate {
         #[arg(short, long, default_value = None)]
         client_id: Option<u64>,
+        /// The id of the ship to fly (see data/ships.ron). The server picks one if not set
+        #[arg(long, default_value = None)]
+        ship: Option<u32>,
+        #[command(flatten)]
+        bots: BotArgs,
     },
     #[cfg(all(feature = "client", feature = "server"))]
     /// Run the app in host-server mode.
@@ -44,50 +71,105 @@ pub enum Mode {
     HostServer {
         #[arg(short, long, default_value = None)]
         client_id: Option<u64>,
+        /// The id of the ship to fly (see data/ships.ron). The server picks one if not set
+        #[arg(long, default_value = None)]
+        ship: Option<u32>,
+        #[command(flatten)]
+        bots: BotArgs,
     },
 }
 
-fn run(cli: Cli) {
-    match cli.mode {
+/// Options to control the bots on the server.
+/// Bots can also be managed at runtime by typing `bots ...` commands in the server's terminal.
+#[cfg(feature = "server")]
+#[derive(Args, Debug)]
+pub struct BotArgs {
+    /// The minimum number of bots in the game
+    #[arg(long, default_value_t = 1)]
+    pub bots: usize,
+    /// Add bots until the number of players + bots reaches this number
+    #[arg(long, default_value = None)]
+    pub fill_bots: Option<usize>,
+    /// The difficulty of the bots: easy, normal or hard
+    #[arg(long, default_value = "normal")]
+    pub bot_difficulty: BotDifficulty,
+    /// Let a bot fly the ship of a disconnected player until they reconnect
+    #[arg(long, default_value_t = false)]
+    pub possess_disconnected: bool,
+    /// How long (in seconds) a disconnected player has to reconnect before their ship is removed
+    #[arg(long, default_value_t = 30)]
+    pub reconnect_grace_secs: u64,
+    /// Whether the bots shoot at their targets (`--bots-shoot false` makes them only chase them)
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub bots_shoot: bool,
+}
+
+#[cfg(feature = "server")]
+impl Default for BotArgs {
+    fn default() -> Self {
+        Self {
+            bots: 1,
+            fill_bots: None,
+            bot_difficulty: BotDifficulty::default(),
+            possess_disconnected: false,
+            reconnect_grace_secs: 30,
+            bots_shoot: true,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl BotArgs {
+    pub fn settings(&self) -> server::BotSettings {
+        server::BotSettings {
+            bot_count: self.bots,
+            fill_player_slots: self.fill_bots,
+            difficulty: self.bot_difficulty,
+            possess_disconnected: self.possess_disconnected,
+            reconnect_grace_period: core::time::Duration::from_secs(self.reconnect_grace_secs),
+            shooting: self.bots_shoot,
+            ..default()
+        }
+    }
+}
+
+fn run(mode: Option<Mode>, network: settings::NetworkSettings) {
+    match mode {
         #[cfg(all(feature = "client", feature = "server"))]
-        Some(Mode::HostServer { client_id }) => {
-            let mut app = host_server::HostServer::new(client_id.unwrap_or(0));
+        Some(Mode::HostServer { client_id, ship, bots }) => {
+            let mut app = host_server::HostServer::new(client_id.unwrap_or(0), ship, bots.settings(), network);
             app.run();
         }
         #[cfg(all(feature = "client", feature = "server"))]
-        Some(Mode::Separate { client_id }) => {
-            let mut app = separate::Separate::new(client_id.unwrap_or(0));
+        Some(Mode::Separate { client_id, ship, bots }) => {
+            let mut app = separate::Separate::new(client_id.unwrap_or(0), ship, bots.settings(), network);
             app.run();
         }
         #[cfg(feature = "client")]
-        Some(Mode::Client { client_id }) => {
-            let mut app = client::ClientApp::new(client_id.unwrap_or(0));
+        Some(Mode::Client { client_id, ship }) => {
+            let mut app = client::ClientApp::new(client_id.unwrap_or(0), ship, network);
             app.run();
         }
         #[cfg(feature = "server")]
-        Some(Mode::Server) => {
-            let mut app = server::ServerApp::new();
+        Some(Mode::Server { game_mode, bots }) => {
+            let mut app = server::ServerApp::new(bots.settings(), game_mode, network);
             app.run();
         }
         None => {
             #[cfg(all(feature = "client", feature = "server"))]
-            run(Cli {
-                mode: Some(Mode::HostServer { client_id: None }),
-            });
+            run(Some(Mode::HostServer { client_id: None, ship: None, bots: BotArgs::default() }), network);
             #[cfg(all(feature = "server", not(feature = "client")))]
-            run(Cli {
-                mode: Some(Mode::Server),
-            });
+            run(Some(Mode::Server { game_mode: GameMode::default(), bots: BotArgs::default() }), network);
 
             #[cfg(all(feature = "client", not(feature = "server")))]
-            run(Cli {
-                mode: Some(Mode::Client { client_id: None }),
-            });
+            run(Some(Mode::Client { client_id: None, ship: None }), network);
         }
     }
 }
 
 fn main() {
     let cli = Cli::parse();
-    run(cli);
+    let network = cli.network.resolve()
+        .unwrap_or_else(|e| Cli::command().error(ErrorKind::ValueValidation, e).exit());
+    run(cli.mode, network);
 }
@@ -1,20 +1,23 @@
 /// HostServer where one thread runs the client and one thread runs the server
-use core::time::Duration;
 use crate::settings;
 use bevy::asset::AssetPlugin;
 use bevy::diagnostic::DiagnosticsPlugin;
 use bevy::prelude::*;
+use bevy::render::mesh::MeshPlugin;
+use bevy::scene::ScenePlugin;
 use bevy::state::app::StatesPlugin;
-use lightyear::prelude::client::{ClientPlugins};
-use lightyear::prelude::server::{ServerPlugins};
-use crate::settings::TICK_RATE;
+use lightyear::prelude::*;
+use lightyear::prelude::client::{Authentication, ClientPlugins};
+use lightyear::prelude::server::{ClientOf, ServerPlugins};
+use shared::prelude::AppState;
+use crate::settings::NetworkSettings;
 
 pub struct Separate {
     client: App,
     server: App,
 }
 impl Separate {
-    pub fn new(client_id: u64) -> Self {
+    pub fn new(client_id: u64, ship: Option<u32>, bot_settings: server::BotSettings, network: NetworkSettings) -> Self {
         // we will communicate between the client and server apps via channels
         let (crossbeam_client, crossbeam_server) = lightyear::crossbeam::CrossbeamIo::new_pair();
 
@@ -36,21 +39,71 @@ impl Separate {
         );
         server_app.add_plugins((
             MinimalPlugins,
-            settings::log_plugin(),
+            // needed to load the map asset
+            AssetPlugin {
+                // https://github.com/bevyengine/bevy/issues/10157
+                meta_check: bevy::asset::AssetMetaCheck::Never,
+                file_path: settings::get_assets_path(),
+                ..default()
+            },
+            // the mesh asset is needed for avian collisions
+            MeshPlugin,
+            ScenePlugin,
+            // the client app already logs
             StatesPlugin,
             DiagnosticsPlugin,
         ));
-        let tick_duration =  Duration::from_secs_f64(1.0 / TICK_RATE);
+        let tick_duration = network.tick_duration();
         client_app.add_plugins(ClientPlugins { tick_duration });
         server_app.add_plugins(ServerPlugins { tick_duration });
         client_app.add_plugins(shared::SharedPlugin { headless: false });
         server_app.add_plugins(shared::SharedPlugin { headless: true });
+        client_app.add_plugins(client::ClientPlugin);
+        client_app.insert_resource(client::ShipChoice(ship));
+        server_app.add_plugins(server::ServerPlugin);
+        server_app.add_observer(settings::replication_sender(network.replication_interval));
+        server_app.insert_resource(bot_settings);
+        server_app.insert_resource(server::MapRotation::new(network.map_rotation.clone()));
+        server_app.add_plugins(crate::console::ConsolePlugin);
+        server_app.add_plugins(crate::token::TokenServicePlugin);
+        server_app.add_plugins(crate::discovery::DiscoveryResponderPlugin);
         client_app.add_plugins(renderer::RendererPlugin);
+        // lets the player join other servers from the menu
+        client_app.add_plugins(crate::connection::ConnectionPlugin { client_id, can_host: false });
 
-        // spawn server
-        server_app.world_mut().spawn(settings::server());
+        // spawn server, it also accepts remote clients
+        let server = server_app.world_mut().spawn(settings::server(network.bind_addr, &network)).id();
+        // the link of the local client on the server side, it is always linked since it's just a channel
+        let conditioner = network.conditioner.clone().map(RecvLinkConditioner::new);
+        server_app.world_mut().spawn((
+            LinkOf { server },
+            ClientOf,
+            Link::new(conditioner),
+            Linked,
+            crossbeam_server,
+            Name::from("LocalClientOf"),
+        ));
 
-        // TODO: spawn local client and client_of using crossbeam IO
+        // spawn the local client, it talks to the server through the channels
+        client_app.world_mut().spawn((
+            // the client runs in the same process as the server, so it can sign its own token with the server's key
+            settings::client_link(
+                Authentication::Manual {
+                    server_addr: network.bind_addr,
+                    client_id,
+                    private_key: network.private_key,
+                    protocol_id: network.protocol_id,
+                },
+                network.bind_addr,
+                &network,
+            ),
+            crossbeam_client,
+            LocalClient,
+            Name::from("Client"),
+        ));
+        client_app.add_systems(Startup, connect_local_client);
+        server_app.insert_resource(network.clone());
+        client_app.insert_resource(network);
 
         Self {
             client: client_app,
@@ -65,6 +118,19 @@ impl Separate {
     }
 }
 
+/// The client that talks to the server running on the other thread
+#[derive(Component)]
+struct LocalClient;
+
+fn connect_local_client(
+    client: Single<Entity, With<LocalClient>>,
+    mut commands: Commands,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    commands.entity(client.into_inner()).trigger(Connect);
+    next_app_state.set(AppState::ConnectingToServer);
+}
+
 /// App that is Send.
 /// Used as a convenient workaround to send an App to a separate thread,
 /// if we know that the App doesn't contain NonSend resources.
@@ -1,4 +1,3 @@
-use std::time::Duration;
 use crate::settings;
 use bevy::asset::AssetPlugin;
 use bevy::diagnostic::DiagnosticsPlugin;
@@ -7,12 +6,13 @@ use bevy::render::mesh::MeshPlugin;
 use bevy::scene::ScenePlugin;
 use bevy::state::app::StatesPlugin;
 use lightyear::prelude::server::*;
-use crate::settings::TICK_RATE;
+use shared::prelude::GameMode;
+use crate::settings::NetworkSettings;
 
 pub struct ServerApp(App);
 
 impl ServerApp {
-    pub fn new() -> Self {
+    pub fn new(bot_settings: server::BotSettings, game_mode: GameMode, network: NetworkSettings) -> Self {
         let mut app = App::new();
         #[cfg(feature = "gui")]
         app.add_plugins(
@@ -46,17 +46,25 @@ impl ServerApp {
             DiagnosticsPlugin,
         ));
 
-        let tick_duration =  Duration::from_secs_f64(1.0 / TICK_RATE);
-        app.add_plugins(ServerPlugins { tick_duration });
+        app.add_plugins(ServerPlugins { tick_duration: network.tick_duration() });
         app.add_plugins(shared::SharedPlugin {
             headless: !cfg!(feature = "gui"),
         });
         app.add_plugins(server::ServerPlugin);
+        app.add_observer(settings::replication_sender(network.replication_interval));
+        app.insert_resource(bot_settings);
+        app.insert_resource(game_mode);
+        app.insert_resource(server::MapRotation::new(network.map_rotation.clone()));
+        app.add_plugins(crate::console::ConsolePlugin);
+        app.add_plugins(crate::token::TokenServicePlugin);
+        app.add_plugins(crate::discovery::DiscoveryResponderPlugin);
         #[cfg(feature = "gui")]
         app.add_plugins(renderer::RendererPlugin);
 
         // spawn server
-        app.world_mut().spawn(settings::server());
+        info!("Server listening on {}", network.bind_addr);
+        app.world_mut().spawn(settings::server(network.bind_addr, &network));
+        app.insert_resource(network);
 
         Self(app)
     }
@@ -9,21 +9,254 @@ use lightyear::prelude::server::*;
 use lightyear::prelude::*;
 #[cfg(feature = "server")]
 use lightyear_examples_common::server::WebTransportCertificateSettings;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+#[cfg(feature = "client")]
+use lightyear::netcode::ConnectToken;
+use clap::Args;
+use serde::Deserialize;
+use shared::prelude::{DEFAULT_MAP, DEFAULT_PORT};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 
-pub const TICK_RATE: f64 = 64.0;
-pub const REPLICATION_INTERVAL: Duration = Duration::from_millis(20);
 pub const ASSETS_HOTRELOAD: bool = true;
 
-pub const SERVER_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 5001));
-pub const LOCAL_SERVER_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 5001));
-pub const PROTOCOL_ID: u64 = 0;
-pub const PRIVATE_KEY: [u8; 32] = [0; 32];
-pub const LINK_CONDITIONER: Option<LinkConditionerConfig> = Some(LinkConditionerConfig {
-    incoming_latency: Duration::from_millis(50),
-    incoming_jitter: Duration::from_millis(5),
-    incoming_loss: 0.05,
-});
+const DEFAULT_PROTOCOL_ID: u64 = 0;
+const DEFAULT_REPLICATION_INTERVAL_MS: u64 = 20;
+/// Updates sent less often than once per second make the game unplayable
+const MAX_REPLICATION_INTERVAL_MS: u64 = 1000;
+const DEFAULT_TICK_RATE: f64 = 64.0;
+const MAX_TICK_RATE: f64 = 240.0;
+const DEFAULT_CONDITIONER_LATENCY_MS: u64 = 50;
+const DEFAULT_CONDITIONER_JITTER_MS: u64 = 5;
+const DEFAULT_CONDITIONER_LOSS: f32 = 0.05;
+const DEFAULT_SERVER_NAME: &str = "LAN game";
+/// Keeps the LAN discovery answers small
+const MAX_SERVER_NAME_LEN: usize = 32;
+
+/// Flags to configure the network and the maps of the server. They override the values from the `--config` file.
+#[derive(Args, Debug, Default)]
+pub struct NetworkArgs {
+    /// RON file with the network settings, the flags below take precedence over it
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// The address the server listens on [default: 0.0.0.0:5001]
+    #[arg(long, global = true)]
+    pub bind: Option<SocketAddr>,
+    /// The server the client joins on startup, instead of showing the title screen
+    #[arg(long, global = true)]
+    pub connect: Option<SocketAddr>,
+    /// The name of the server in the LAN server browser [default: "LAN game"]
+    #[arg(long, global = true)]
+    pub server_name: Option<String>,
+    /// Simulation ticks per second, the client and the server must use the same value [default: 64]
+    #[arg(long, global = true)]
+    pub tick_rate: Option<f64>,
+    /// Clients only join and list the servers with the same protocol id, change it to keep incompatible builds apart [default: 0]
+    #[arg(long, global = true)]
+    pub protocol_id: Option<u64>,
+    /// How often (in ms) the server sends the state of the game to the clients [default: 20]
+    #[arg(long, global = true)]
+    pub replication_interval: Option<u64>,
+    /// File with the 32 bytes private key the server signs connect tokens with, as raw bytes or 64 hex characters [default: a random key]
+    #[arg(long, global = true)]
+    pub key_file: Option<PathBuf>,
+    /// Latency (in ms) added to incoming packets by the link conditioner [default: 50]
+    #[arg(long, global = true)]
+    pub latency: Option<u64>,
+    /// Random jitter (in ms) added to incoming packets by the link conditioner [default: 5]
+    #[arg(long, global = true)]
+    pub jitter: Option<u64>,
+    /// Fraction of incoming packets dropped by the link conditioner, between 0.0 and 1.0 [default: 0.05]
+    #[arg(long, global = true)]
+    pub packet_loss: Option<f32>,
+    /// Disable the link conditioner
+    #[arg(long, global = true)]
+    pub no_conditioner: bool,
+    /// A map the server plays, repeat it to play several maps in rotation: `--map m1 --map m2` [default: m4]
+    #[arg(long = "map", global = true)]
+    pub maps: Vec<String>,
+}
+
+/// The content of the `--config` file, every field is optional
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct NetworkConfig {
+    bind: Option<SocketAddr>,
+    connect: Option<SocketAddr>,
+    server_name: Option<String>,
+    tick_rate: Option<f64>,
+    protocol_id: Option<u64>,
+    replication_interval_ms: Option<u64>,
+    key_file: Option<PathBuf>,
+    conditioner: ConditionerConfig,
+    maps: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct ConditionerConfig {
+    enabled: bool,
+    latency_ms: Option<u64>,
+    jitter_ms: Option<u64>,
+    loss: Option<f32>,
+}
+
+impl Default for ConditionerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            latency_ms: None,
+            jitter_ms: None,
+            loss: None,
+        }
+    }
+}
+
+/// The validated network settings of the app
+#[derive(Resource, Debug, Clone)]
+pub struct NetworkSettings {
+    /// The address the server listens on
+    pub bind_addr: SocketAddr,
+    /// The server the client joins on startup
+    pub connect_addr: Option<SocketAddr>,
+    /// How the server shows up in the LAN server browser
+    pub server_name: String,
+    pub tick_rate: f64,
+    pub protocol_id: u64,
+    /// How often the server sends updates to each client
+    pub replication_interval: Duration,
+    pub private_key: [u8; 32],
+    pub conditioner: Option<LinkConditionerConfig>,
+    /// The maps the server plays, in order
+    pub map_rotation: Vec<String>,
+}
+
+impl NetworkSettings {
+    pub fn tick_duration(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate)
+    }
+}
+
+impl NetworkArgs {
+    /// Merge the flags with the config file and check that the result makes sense
+    pub fn resolve(&self) -> Result<NetworkSettings, String> {
+        let config = match &self.config {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read the config file {}: {e}", path.display()))?;
+                ron::from_str::<NetworkConfig>(&contents)
+                    .map_err(|e| format!("Invalid config file {}: {e}", path.display()))?
+            }
+            None => NetworkConfig::default(),
+        };
+
+        let bind_addr = self.bind.or(config.bind)
+            .unwrap_or(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), DEFAULT_PORT));
+        if bind_addr.port() == 0 || bind_addr.port() == u16::MAX {
+            return Err(format!("The server needs a fixed port to bind to, followed by a free port for its token service, got {bind_addr}"));
+        }
+
+        let connect_addr = self.connect.or(config.connect);
+        if let Some(addr) = connect_addr {
+            if addr.ip().is_unspecified() || addr.port() == 0 {
+                return Err(format!("Cannot connect to {addr}, use the address and port of the server"));
+            }
+        }
+
+        let server_name = self.server_name.clone().or(config.server_name)
+            .unwrap_or_else(|| DEFAULT_SERVER_NAME.to_string());
+        let server_name = server_name.trim().to_string();
+        if server_name.is_empty() || server_name.chars().count() > MAX_SERVER_NAME_LEN {
+            return Err(format!("The server name must have between 1 and {MAX_SERVER_NAME_LEN} characters, got {server_name:?}"));
+        }
+
+        let tick_rate = self.tick_rate.or(config.tick_rate).unwrap_or(DEFAULT_TICK_RATE);
+        if !(tick_rate.is_finite() && tick_rate > 0.0 && tick_rate <= MAX_TICK_RATE) {
+            return Err(format!("The tick rate must be above 0 and at most {MAX_TICK_RATE}, got {tick_rate}"));
+        }
+
+        let protocol_id = self.protocol_id.or(config.protocol_id).unwrap_or(DEFAULT_PROTOCOL_ID);
+
+        let replication_interval_ms = self.replication_interval.or(config.replication_interval_ms).unwrap_or(DEFAULT_REPLICATION_INTERVAL_MS);
+        if replication_interval_ms == 0 || replication_interval_ms > MAX_REPLICATION_INTERVAL_MS {
+            return Err(format!("The replication interval must be between 1 and {MAX_REPLICATION_INTERVAL_MS}ms, got {replication_interval_ms}ms"));
+        }
+        let replication_interval = Duration::from_millis(replication_interval_ms);
+
+        let private_key = match self.key_file.as_ref().or(config.key_file.as_ref()) {
+            Some(path) => read_private_key(path)?,
+            // only the server knows the key, so the only way to get a connect token is to ask its token service
+            None => rand::random(),
+        };
+
+        let conditioner = if self.no_conditioner || !config.conditioner.enabled {
+            None
+        } else {
+            let latency = self.latency.or(config.conditioner.latency_ms).unwrap_or(DEFAULT_CONDITIONER_LATENCY_MS);
+            let jitter = self.jitter.or(config.conditioner.jitter_ms).unwrap_or(DEFAULT_CONDITIONER_JITTER_MS);
+            let loss = self.packet_loss.or(config.conditioner.loss).unwrap_or(DEFAULT_CONDITIONER_LOSS);
+            if jitter > latency {
+                return Err(format!("The jitter ({jitter}ms) cannot be larger than the latency ({latency}ms)"));
+            }
+            if !(0.0..=1.0).contains(&loss) {
+                return Err(format!("The packet loss must be between 0.0 and 1.0, got {loss}"));
+            }
+            Some(LinkConditionerConfig {
+                incoming_latency: Duration::from_millis(latency),
+                incoming_jitter: Duration::from_millis(jitter),
+                incoming_loss: loss,
+            })
+        };
+
+        let map_rotation = if self.maps.is_empty() {
+            config.maps.unwrap_or_else(|| vec![DEFAULT_MAP.to_string()])
+        } else {
+            self.maps.clone()
+        };
+        if map_rotation.is_empty() {
+            return Err("The map rotation needs at least one map".to_string());
+        }
+        for map in map_rotation.iter() {
+            let path = Path::new(&get_assets_path()).join("maps").join(format!("{map}.map"));
+            if !path.is_file() {
+                return Err(format!("Unknown map {map:?}, there is no {}", path.display()));
+            }
+        }
+
+        Ok(NetworkSettings {
+            bind_addr,
+            connect_addr,
+            server_name,
+            tick_rate,
+            protocol_id,
+            replication_interval,
+            private_key,
+            conditioner,
+            map_rotation,
+        })
+    }
+}
+
+/// Read a private key, stored either as 32 raw bytes or as 64 hex characters
+fn read_private_key(path: &Path) -> Result<[u8; 32], String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read the key file {}: {e}", path.display()))?;
+
+    let text = String::from_utf8_lossy(&bytes);
+    let hex = text.trim();
+    if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        let mut key = [0; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        return Ok(key);
+    }
+
+    let len = bytes.len();
+    bytes.try_into().map_err(|_| format!(
+        "The key file {} must contain 32 bytes or 64 hex characters, found {len} bytes",
+        path.display()
+    ))
+}
 
 pub(crate) fn get_assets_path() -> String {
     const ASSETS_PATH: &'static str = "../assets";
@@ -38,21 +271,9 @@ pub(crate) fn get_assets_path() -> String {
         .to_string()
 }
 
+/// A client that joins a remote server with a connect token from its token service, see [`crate::token`]
 #[cfg(feature = "client")]
-pub(crate) fn client(client_id: u64) -> impl Bundle {
-    let client_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
-    let auth = Authentication::Manual {
-            server_addr: SERVER_ADDR,
-            client_id,
-            private_key: PRIVATE_KEY,
-            protocol_id: PROTOCOL_ID,
-    };
-    let netcode_config = client::NetcodeConfig {
-        // Make sure that the server times out clients when their connection is closed
-        client_timeout_secs: 3,
-        token_expire_secs: -1,
-        ..default()
-    };
+pub(crate) fn client(token: ConnectToken, server_addr: SocketAddr, settings: &NetworkSettings) -> impl Bundle {
     let certificate_digest = {
         #[cfg(target_family = "wasm")]
         {
@@ -63,32 +284,47 @@ pub(crate) fn client(client_id: u64) -> impl Bundle {
             "".to_string()
         }
     };
-    let conditioner = LINK_CONDITIONER.map(|c| RecvLinkConditioner::new(c));
+    (
+        client_link(Authentication::Token(token), server_addr, settings),
+        WebTransportClientIo { certificate_digest },
+        Name::from("Client"),
+    )
+}
+
+/// Everything a client needs except the IO, so that the same client can also talk to a server through channels
+#[cfg(feature = "client")]
+pub(crate) fn client_link(auth: Authentication, server_addr: SocketAddr, settings: &NetworkSettings) -> impl Bundle {
+    let client_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
+    let netcode_config = client::NetcodeConfig {
+        // Make sure that the server times out clients when their connection is closed
+        client_timeout_secs: 3,
+        token_expire_secs: -1,
+        ..default()
+    };
+    let conditioner = settings.conditioner.clone().map(RecvLinkConditioner::new);
     (
         Client::default(),
         Link::new(conditioner),
         LocalAddr(client_addr),
-        PeerAddr(SERVER_ADDR),
+        PeerAddr(server_addr),
         ReplicationReceiver::default(),
         PredictionManager::default(),
         InterpolationManager::default(),
         NetcodeClient::new(auth, netcode_config).unwrap(),
-        WebTransportClientIo { certificate_digest },
-        Name::from("Client"),
     )
 }
 
 #[cfg(feature = "server")]
-pub(crate) fn server() -> impl Bundle {
+pub(crate) fn server(bind_addr: SocketAddr, settings: &NetworkSettings) -> impl Bundle {
     let certificate = &WebTransportCertificateSettings::FromFile {
         cert: "certificates/cert.pem".to_string(),
         key: "certificates/key.pem".to_string(),
     };
     (
-        LocalAddr(LOCAL_SERVER_ADDR),
+        LocalAddr(bind_addr),
         NetcodeServer::new(server::NetcodeConfig {
-            protocol_id: PROTOCOL_ID,
-            private_key: PRIVATE_KEY,
+            protocol_id: settings.protocol_id,
+            private_key: settings.private_key,
             ..Default::default()
         }),
         WebTransportServerIo {
@@ -98,6 +334,15 @@ pub(crate) fn server() -> impl Bundle {
     )
 }
 
+/// An observer that makes the server replicate to every new client at the configured interval
+#[cfg(feature = "server")]
+pub(crate) fn replication_sender(interval: Duration) -> impl Fn(Trigger<OnAdd, LinkOf>, Commands) {
+    move |trigger, mut commands| {
+        commands.entity(trigger.target())
+            .insert(ReplicationSender::new(interval, SendUpdatesMode::SinceLastAck, false));
+    }
+}
+
 #[cfg(feature = "gui")]
 pub(crate) fn window_plugin() -> WindowPlugin {
     WindowPlugin {
@@ -0,0 +1,280 @@
+//! Connect tokens, so that clients can join a server without knowing its private key.
+//!
+//! While a server is started, a token service listens over TCP on the port right after the game port.
+//! A client sends its id as 8 little endian bytes followed by its 32 bytes secret, and gets back a status byte,
+//! followed by the length (u32, little endian) and the bytes of a connect token signed with the server's key.
+//!
+//! The first client that asks for an id claims it with its secret, until the server stops.
+//! Nobody else gets a token for that id, so a player cannot be impersonated while they are offline.
+//! The secret is generated once per user and kept in their config directory.
+use core::time::Duration;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use lightyear::netcode::ConnectToken;
+
+/// The token service of a server listens on the game port + 1
+pub(crate) fn token_service_addr(game_addr: SocketAddr) -> Option<SocketAddr> {
+    let port = game_addr.port().checked_add(1)?;
+    Some(SocketAddr::new(game_addr.ip(), port))
+}
+
+/// How long a client has to connect after getting its token
+#[cfg(feature = "server")]
+const TOKEN_EXPIRE_SECS: i32 = 30;
+/// A peer that doesn't finish its request in time is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+const STATUS_OK: u8 = 0;
+const STATUS_DUPLICATE_ID: u8 = 1;
+#[cfg(feature = "server")]
+const STATUS_ERROR: u8 = 2;
+const STATUS_CLAIMED_ID: u8 = 3;
+
+/// Proves that a client is the player who first used its id on a server
+type ClientSecret = [u8; 32];
+/// Netcode tokens are 2048 bytes, anything much larger is not a token
+#[cfg(feature = "client")]
+const MAX_TOKEN_BYTES: usize = 4096;
+
+#[cfg(feature = "server")]
+pub(crate) use service::TokenServicePlugin;
+
+#[cfg(feature = "server")]
+mod service {
+    use std::collections::{HashMap, HashSet};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use bevy::prelude::*;
+    use lightyear::prelude::*;
+    use lightyear::prelude::server::*;
+
+    use super::*;
+    use crate::settings::NetworkSettings;
+
+    /// Runs a token service next to every started server
+    pub(crate) struct TokenServicePlugin;
+
+    impl Plugin for TokenServicePlugin {
+        fn build(&self, app: &mut App) {
+            app.add_observer(start_token_service);
+            app.add_observer(stop_token_service);
+            app.add_systems(Update, update_connected_ids_system.run_if(any_with_component::<TokenService>));
+        }
+    }
+
+    /// Lives on the server entity while its token service is running
+    #[derive(Component)]
+    struct TokenService {
+        /// The ids of the connected clients, the service refuses to hand out tokens for them
+        connected_ids: Arc<Mutex<HashSet<u64>>>,
+        /// The secret of the client that first asked for each id
+        claimed_ids: Arc<Mutex<HashMap<u64, ClientSecret>>>,
+        shutdown: Arc<AtomicBool>,
+    }
+
+    impl Drop for TokenService {
+        fn drop(&mut self) {
+            self.shutdown.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn start_token_service(
+        trigger: Trigger<OnAdd, Started>,
+        servers: Query<&LocalAddr, With<Server>>,
+        network: Res<NetworkSettings>,
+        mut commands: Commands,
+    ) {
+        let Ok(local_addr) = servers.get(trigger.target()) else { return };
+        let game_addr = local_addr.0;
+        let Some(addr) = token_service_addr(game_addr) else {
+            error!("No port left for the token service after the game port {}, clients will not be able to join", game_addr.port());
+            return;
+        };
+        let listener = match TcpListener::bind(addr).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        }) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to start the token service on {addr}, clients will not be able to join: {e}");
+                return;
+            }
+        };
+        info!("Handing out connect tokens on {addr}");
+
+        let service = TokenService {
+            connected_ids: default(),
+            claimed_ids: default(),
+            shutdown: default(),
+        };
+        let connected_ids = service.connected_ids.clone();
+        let claimed_ids = service.claimed_ids.clone();
+        let shutdown = service.shutdown.clone();
+        let private_key = network.private_key;
+        let protocol_id = network.protocol_id;
+        std::thread::spawn(move || {
+            run_token_service(listener, game_addr.port(), protocol_id, private_key, connected_ids, claimed_ids, shutdown)
+        });
+        commands.entity(trigger.target()).insert(service);
+    }
+
+    fn stop_token_service(
+        trigger: Trigger<OnAdd, Stopped>,
+        mut commands: Commands,
+    ) {
+        // dropping the component shuts the service down
+        commands.entity(trigger.target()).remove::<TokenService>();
+    }
+
+    fn update_connected_ids_system(
+        services: Query<&TokenService>,
+        clients: Query<&RemoteId, (With<ClientOf>, With<Connected>)>,
+    ) {
+        for service in services.iter() {
+            let mut connected_ids = service.connected_ids.lock().unwrap();
+            connected_ids.clear();
+            connected_ids.extend(clients.iter().filter_map(|remote_id| match remote_id.0 {
+                PeerId::Netcode(id) => Some(id),
+                _ => None,
+            }));
+        }
+    }
+
+    fn run_token_service(
+        listener: TcpListener,
+        game_port: u16,
+        protocol_id: u64,
+        private_key: [u8; 32],
+        connected_ids: Arc<Mutex<HashSet<u64>>>,
+        claimed_ids: Arc<Mutex<HashMap<u64, ClientSecret>>>,
+        shutdown: Arc<AtomicBool>,
+    ) {
+        while !shutdown.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, peer_addr)) => {
+                    let connected_ids = connected_ids.clone();
+                    let claimed_ids = claimed_ids.clone();
+                    // a slow peer must not hold up the others
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle_request(stream, game_port, protocol_id, private_key, &connected_ids, &claimed_ids) {
+                            warn!("Failed to hand out a connect token to {peer_addr}: {e}");
+                        }
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => warn!("Token service failed to accept a connection: {e}"),
+            }
+        }
+    }
+
+    fn handle_request(
+        mut stream: TcpStream,
+        game_port: u16,
+        protocol_id: u64,
+        private_key: [u8; 32],
+        connected_ids: &Mutex<HashSet<u64>>,
+        claimed_ids: &Mutex<HashMap<u64, ClientSecret>>,
+    ) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+        let mut client_id = [0; 8];
+        stream.read_exact(&mut client_id)?;
+        let client_id = u64::from_le_bytes(client_id);
+        let mut secret = ClientSecret::default();
+        stream.read_exact(&mut secret)?;
+
+        if *claimed_ids.lock().unwrap().entry(client_id).or_insert(secret) != secret {
+            info!("Refusing a connect token to client {client_id}: the id belongs to another player");
+            return stream.write_all(&[STATUS_CLAIMED_ID]);
+        }
+        if connected_ids.lock().unwrap().contains(&client_id) {
+            info!("Refusing a connect token to client {client_id}: the id is already in use");
+            return stream.write_all(&[STATUS_DUPLICATE_ID]);
+        }
+
+        // the client reached us on this address, so it can reach the game on it too
+        let server_addr = SocketAddr::new(stream.local_addr()?.ip(), game_port);
+        let token = ConnectToken::build(server_addr, protocol_id, client_id, private_key)
+            .expire_seconds(TOKEN_EXPIRE_SECS)
+            .generate()
+            .map_err(|e| io::Error::other(format!("{e:?}")))
+            .and_then(|token| token.try_into_bytes());
+        match token {
+            Ok(token) => {
+                stream.write_all(&[STATUS_OK])?;
+                stream.write_all(&(token.len() as u32).to_le_bytes())?;
+                stream.write_all(&token)
+            }
+            Err(e) => {
+                stream.write_all(&[STATUS_ERROR])?;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Ask the token service of the server at `server_addr` for a connect token. Blocks until it answers.
+#[cfg(feature = "client")]
+pub(crate) fn request_connect_token(server_addr: SocketAddr, client_id: u64) -> Result<ConnectToken, String> {
+    let Some(addr) = token_service_addr(server_addr) else {
+        return Err(format!("The server at {server_addr} has no token service"));
+    };
+    let request = || -> io::Result<Result<ConnectToken, String>> {
+        let mut stream = TcpStream::connect_timeout(&addr, REQUEST_TIMEOUT)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.write_all(&client_id.to_le_bytes())?;
+        stream.write_all(&client_secret())?;
+
+        let mut status = [0; 1];
+        stream.read_exact(&mut status)?;
+        match status[0] {
+            STATUS_OK => {}
+            STATUS_DUPLICATE_ID => return Ok(Err(format!(
+                "Another player already uses the client id {client_id}, pick another one with --client-id"
+            ))),
+            STATUS_CLAIMED_ID => return Ok(Err(format!(
+                "The client id {client_id} belongs to another player on this server, pick another one with --client-id"
+            ))),
+            _ => return Ok(Err("The server failed to create a connect token".to_string())),
+        }
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_TOKEN_BYTES {
+            return Ok(Err(format!("The server sent a connect token of {len} bytes")));
+        }
+        let mut token = vec![0; len];
+        stream.read_exact(&mut token)?;
+        Ok(ConnectToken::try_from_bytes(&token)
+            .map_err(|e| format!("The server sent an invalid connect token: {e:?}")))
+    };
+    request().unwrap_or_else(|e| Err(format!("Could not reach the server at {server_addr}: {e}")))
+}
+
+/// Where the secret of the local player is kept
+#[cfg(feature = "client")]
+const CLIENT_SECRET_FILE: &str = "client_secret";
+
+/// The secret of the local player, generated the first time it is needed.
+/// If it cannot be saved, the player loses their ids once the game is restarted.
+#[cfg(feature = "client")]
+fn client_secret() -> ClientSecret {
+    let path = client::user_config_path(CLIENT_SECRET_FILE);
+    if let Ok(Ok(secret)) = std::fs::read(&path).map(ClientSecret::try_from) {
+        return secret;
+    }
+    let secret: ClientSecret = rand::random();
+    if let Err(e) = std::fs::write(&path, secret) {
+        bevy::log::warn!("Failed to save the client secret to {}: {e}", path.display());
+    }
+    secret
+}
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
+use shared::prelude::{AppState, ChatMessage, ChatScope, PlayingSubState, SendChatMessage, MAX_CHAT_MESSAGE_LEN};
+
+/// The chat box in the bottom left corner of the screen.
+/// T opens it to talk to everyone, Y to talk to the team, Enter sends and Escape closes it.
+pub(crate) struct ChatBoxPlugin;
+
+impl Plugin for ChatBoxPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatBox>();
+        app.add_observer(chat_message_received);
+        app.add_systems(OnEnter(AppState::TitleScreen), clear_chat_system);
+        app.add_systems(Update, (
+            open_chat_system.run_if(in_state(AppState::Playing(PlayingSubState::Playing))),
+            chat_box_system.run_if(
+                in_state(AppState::Playing(PlayingSubState::Playing))
+                    .or(in_state(AppState::Playing(PlayingSubState::Chat)))
+                    .or(in_state(AppState::Playing(PlayingSubState::Menu)))
+            ),
+        ).chain());
+    }
+}
+
+/// How many messages the chat box remembers
+const CHAT_HISTORY_LEN: usize = 50;
+/// How many messages are shown while the chat box is closed
+const CHAT_VISIBLE_LINES: usize = 6;
+/// How long (in seconds) a message stays on screen while the chat box is closed
+const CHAT_FADE_SECS: f32 = 10.0;
+
+struct ChatLine {
+    message: ChatMessage,
+    /// When the message arrived, in seconds since startup
+    received_at: f32,
+}
+
+#[derive(Resource, Default)]
+struct ChatBox {
+    history: VecDeque<ChatLine>,
+    /// The message being typed
+    input: String,
+    scope: ChatScope,
+    /// Set when the chat box was just opened, to give the focus to the text field
+    focus: bool,
+}
+
+fn chat_message_received(
+    trigger: Trigger<ChatMessage>,
+    time: Res<Time<Real>>,
+    mut chat_box: ResMut<ChatBox>,
+) {
+    chat_box.history.push_back(ChatLine {
+        message: trigger.event().clone(),
+        received_at: time.elapsed_secs(),
+    });
+    while chat_box.history.len() > CHAT_HISTORY_LEN {
+        chat_box.history.pop_front();
+    }
+}
+
+/// Forget the chat of the previous game
+fn clear_chat_system(mut chat_box: ResMut<ChatBox>) {
+    *chat_box = ChatBox::default();
+}
+
+fn open_chat_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut chat_box: ResMut<ChatBox>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    let scope = if keyboard_input.just_pressed(KeyCode::KeyT) {
+        ChatScope::All
+    } else if keyboard_input.just_pressed(KeyCode::KeyY) {
+        ChatScope::Team
+    } else {
+        return;
+    };
+    chat_box.scope = scope;
+    chat_box.input.clear();
+    chat_box.focus = true;
+    next_app_state.set(AppState::Playing(PlayingSubState::Chat));
+}
+
+fn chat_box_system(
+    mut commands: Commands,
+    mut ctx: EguiContexts,
+    time: Res<Time<Real>>,
+    current_app_state: Res<State<AppState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut chat_box: ResMut<ChatBox>,
+) {
+    let is_typing = *current_app_state.get() == AppState::Playing(PlayingSubState::Chat);
+    let now = time.elapsed_secs();
+    let visible_lines = if is_typing {
+        chat_box.history.len()
+    } else {
+        chat_box.history.iter().rev()
+            .take(CHAT_VISIBLE_LINES)
+            .take_while(|line| now - line.received_at < CHAT_FADE_SECS)
+            .count()
+    };
+    if !is_typing && visible_lines == 0 {
+        return;
+    }
+
+    let ctx = ctx.ctx_mut();
+    egui::Area::new(egui::Id::new("chat_box"))
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10.0, -10.0))
+        .show(ctx, |ui| {
+            ui.set_max_width(400.0);
+            let frame = if is_typing {
+                egui::Frame::popup(ui.style())
+            } else {
+                egui::Frame::NONE
+            };
+            frame.show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        let start = chat_box.history.len() - visible_lines;
+                        for line in chat_box.history.iter().skip(start) {
+                            chat_line_ui(ui, &line.message);
+                        }
+                    });
+
+                if !is_typing {
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    ui.label(match chat_box.scope {
+                        ChatScope::All => "All:",
+                        ChatScope::Team => "Team:",
+                    });
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut chat_box.input)
+                            .char_limit(MAX_CHAT_MESSAGE_LEN)
+                            .desired_width(f32::INFINITY),
+                    );
+                    if chat_box.focus {
+                        response.request_focus();
+                        chat_box.focus = false;
+                    }
+                    let (enter, escape) = ui.input(|i| (i.key_pressed(egui::Key::Enter), i.key_pressed(egui::Key::Escape)));
+                    if enter || escape {
+                        let text = core::mem::take(&mut chat_box.input);
+                        if enter && !text.trim().is_empty() {
+                            commands.trigger(SendChatMessage {
+                                scope: chat_box.scope,
+                                text,
+                            });
+                        }
+                        next_app_state.set(AppState::Playing(PlayingSubState::Playing));
+                    }
+                });
+            });
+        });
+}
+
+fn chat_line_ui(ui: &mut egui::Ui, message: &ChatMessage) {
+    match &message.author {
+        // messages from the server itself: joins, leaves, kills...
+        None => {
+            ui.colored_label(egui::Color32::YELLOW, message.text.as_str());
+        }
+        Some(author) => {
+            let (prefix, color) = match message.scope {
+                ChatScope::All => ("", egui::Color32::WHITE),
+                ChatScope::Team => ("[Team] ", egui::Color32::LIGHT_BLUE),
+            };
+            ui.colored_label(color, format!("{prefix}{author}: {}", message.text));
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use core::f32::consts::TAU;
+
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use shared::prelude::{DamageTaken, Damageable, PlayerShip, Ship, ShipsData};
+
+/// Tells the player that they are getting hurt:
+/// - arcs around the crosshair point towards whoever damaged us
+/// - the edges of the screen flash red
+/// - a warning blinks while the health is low
+pub(crate) struct DamageIndicatorsPlugin;
+
+impl Plugin for DamageIndicatorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(spawn_damage_overlay_system);
+        app.add_observer(damage_taken_system);
+        app.add_systems(Update, (
+            damage_arcs_system,
+            damage_flash_system,
+            low_health_warning_system.run_if(resource_exists::<ShipsData>),
+        ));
+    }
+}
+
+/// How long (in seconds) an arc stays on screen
+const DAMAGE_ARC_SECS: f32 = 1.5;
+/// Distance (in pixels) between the center of the screen and the arcs
+const DAMAGE_ARC_RADIUS: f32 = 140.0;
+const DAMAGE_ARC_WIDTH: f32 = 110.0;
+const DAMAGE_ARC_HEIGHT: f32 = 10.0;
+/// How much a point of damage flashes the screen edges, the flash is capped at 1.0
+const DAMAGE_FLASH_PER_DAMAGE: f32 = 0.03;
+/// How much of the flash goes away per second
+const DAMAGE_FLASH_DECAY: f32 = 2.0;
+const DAMAGE_FLASH_BORDER: f32 = 48.0;
+/// The warning shows below this fraction of the ship's starting health
+const LOW_HEALTH_FRACTION: f32 = 0.25;
+/// How many times per second the warning blinks
+const LOW_HEALTH_BLINK_HZ: f32 = 2.0;
+
+const DAMAGE_COLOR: Color = Color::srgb(0.9, 0.05, 0.0);
+
+/// Full screen node holding the arcs, its border is the red flash
+#[derive(Component, Debug, Default)]
+struct DamageOverlay {
+    /// between 0.0 and 1.0
+    flash: f32,
+}
+
+#[derive(Component, Debug)]
+struct DamageArc {
+    attacker_position: Vec3,
+    timer: Timer,
+}
+
+#[derive(Component, Debug)]
+struct LowHealthWarning;
+
+fn spawn_damage_overlay_system(
+    _: Trigger<OnAdd, Camera3d>,
+    overlays: Query<(), With<DamageOverlay>>,
+    mut commands: Commands,
+) {
+    // the camera is spawned again with every ship
+    if !overlays.is_empty() {
+        return;
+    }
+    commands.spawn((
+        DamageOverlay::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            border: UiRect::all(Val::Px(DAMAGE_FLASH_BORDER)),
+            ..default()
+        },
+        BorderColor(Color::NONE),
+    ));
+    commands.spawn((
+        LowHealthWarning,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            top: Val::Percent(65.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Visibility::Hidden,
+    )).with_child((
+        Text::new("LOW HEALTH"),
+        TextFont::from_font_size(32.0),
+        TextColor(DAMAGE_COLOR),
+    ));
+}
+
+fn damage_taken_system(
+    trigger: Trigger<DamageTaken>,
+    mut commands: Commands,
+    mut overlay: Query<(Entity, &mut DamageOverlay)>,
+) {
+    let Ok((overlay_entity, mut overlay)) = overlay.single_mut() else { return };
+    let damage = trigger.event();
+    overlay.flash = (overlay.flash + damage.damage as f32 * DAMAGE_FLASH_PER_DAMAGE).min(1.0);
+
+    commands.entity(overlay_entity).with_child((
+        DamageArc {
+            attacker_position: damage.attacker_position,
+            timer: Timer::from_seconds(DAMAGE_ARC_SECS, TimerMode::Once),
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(50.0),
+            top: Val::Percent(50.0),
+            width: Val::Px(DAMAGE_ARC_WIDTH),
+            height: Val::Px(DAMAGE_ARC_HEIGHT),
+            ..default()
+        },
+        BackgroundColor(DAMAGE_COLOR),
+        BorderRadius::MAX,
+    ));
+}
+
+/// Keep the arcs pointed at the attackers while the ship turns, and fade them out
+fn damage_arcs_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    camera: Query<&GlobalTransform, With<Camera3d>>,
+    mut arcs: Query<(Entity, &mut DamageArc, &mut Node, &mut Transform, &mut BackgroundColor)>,
+) {
+    let Ok(camera) = camera.single() else { return };
+    for (entity, mut arc, mut node, mut transform, mut color) in arcs.iter_mut() {
+        if arc.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // where the attacker is, projected on the screen plane. 0 is straight up, clockwise
+        let local = camera.rotation().inverse() * (arc.attacker_position - camera.translation());
+        let angle = if local.truncate().length_squared() > f32::EPSILON {
+            local.x.atan2(local.y)
+        } else {
+            0.0
+        };
+        let offset = Vec2::new(angle.sin(), -angle.cos()) * DAMAGE_ARC_RADIUS;
+        node.margin = UiRect {
+            left: Val::Px(offset.x - DAMAGE_ARC_WIDTH / 2.0),
+            top: Val::Px(offset.y - DAMAGE_ARC_HEIGHT / 2.0),
+            ..default()
+        };
+        // the layout only sets the translation, the rotation makes the arc tangent to the circle
+        transform.rotation = Quat::from_rotation_z(angle.rem_euclid(TAU));
+        color.0.set_alpha(1.0 - arc.timer.fraction());
+    }
+}
+
+fn damage_flash_system(
+    time: Res<Time>,
+    mut overlay: Query<(&mut DamageOverlay, &mut BorderColor)>,
+) {
+    for (mut overlay, mut border) in overlay.iter_mut() {
+        overlay.flash = (overlay.flash - DAMAGE_FLASH_DECAY * time.delta_secs()).max(0.0);
+        border.0 = DAMAGE_COLOR.with_alpha(overlay.flash * 0.6);
+    }
+}
+
+/// Driven by the replicated health of our ship
+fn low_health_warning_system(
+    time: Res<Time>,
+    ships_data: Res<ShipsData>,
+    controlled_player: Query<(&Damageable, &Ship), (With<PlayerShip>, With<Predicted>)>,
+    mut warning: Query<(&mut Visibility, &Children), With<LowHealthWarning>>,
+    mut texts: Query<&mut TextColor>,
+) {
+    let Ok((mut visibility, children)) = warning.single_mut() else { return };
+    let low_health = controlled_player.single().ok().and_then(|(damageable, ship)| {
+        let starting_health = ships_data.ships.get(&ship.0)?.starting_health;
+        let fraction = damageable.health as f32 / starting_health.max(1) as f32;
+        Some(damageable.health > 0 && fraction < LOW_HEALTH_FRACTION)
+    }).unwrap_or(false);
+
+    *visibility = if low_health { Visibility::Inherited } else { Visibility::Hidden };
+    if low_health {
+        let blink = 0.5 + 0.5 * (time.elapsed_secs() * LOW_HEALTH_BLINK_HZ * TAU).sin();
+        for child in children.iter() {
+            if let Ok(mut color) = texts.get_mut(child) {
+                color.0.set_alpha(0.3 + 0.7 * blink);
+            }
+        }
+    }
+}
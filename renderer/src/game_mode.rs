@@ -0,0 +1,129 @@
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
+use shared::prelude::{GameMode, KeyColor, KeyPickup, MatchPhase, MatchResult, MatchState, Team};
+
+/// The match timer and results at the top of the screen, and the keys waiting to be picked up in co-op
+pub(crate) struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(spawn_key_pickup_visuals_system);
+        app.add_systems(Update, (
+            spin_key_pickups_system,
+            match_status_system,
+        ));
+    }
+}
+
+/// How fast (in radians per second) the keys spin
+const KEY_SPIN_SPEED: f32 = 2.0;
+
+pub(crate) fn key_color(key: KeyColor) -> Color {
+    match key {
+        KeyColor::Red => Color::srgb(1.0, 0.1, 0.1),
+        KeyColor::Blue => Color::srgb(0.1, 0.3, 1.0),
+        KeyColor::Yellow => Color::srgb(1.0, 0.9, 0.1),
+    }
+}
+
+pub(crate) fn team_color(team: Team) -> egui::Color32 {
+    match team {
+        Team::Red => egui::Color32::from_rgb(255, 80, 80),
+        Team::Blue => egui::Color32::from_rgb(80, 140, 255),
+    }
+}
+
+fn spawn_key_pickup_visuals_system(
+    trigger: Trigger<OnAdd, KeyPickup>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    keys: Query<&KeyPickup>,
+) {
+    let Ok(key) = keys.get(trigger.target()) else { return };
+    let color: LinearRgba = key_color(key.color).into();
+    commands.entity(trigger.target()).insert((
+        Mesh3d(meshes.add(Mesh::from(Cuboid::new(0.3, 0.6, 0.1)))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: color.into(),
+            emissive: color,
+            ..Default::default()
+        })),
+        Transform::from_translation(key.position),
+        Visibility::default(),
+        NotShadowCaster,
+    ));
+}
+
+fn spin_key_pickups_system(
+    time: Res<Time>,
+    mut keys: Query<&mut Transform, With<KeyPickup>>,
+) {
+    for mut transform in keys.iter_mut() {
+        transform.rotate_y(KEY_SPIN_SPEED * time.delta_secs());
+    }
+}
+
+fn format_time(secs: u32) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn match_status_system(
+    mut ctx: EguiContexts,
+    match_state: Query<&MatchState>,
+) {
+    let Ok(state) = match_state.single() else { return };
+
+    let ctx = ctx.ctx_mut();
+    egui::Area::new(egui::Id::new("match_status"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 10.0))
+        .show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                match state.phase {
+                    MatchPhase::Warmup => {
+                        ui.colored_label(egui::Color32::WHITE, "Warmup");
+                        match state.time_left {
+                            Some(secs) => ui.colored_label(egui::Color32::WHITE, format!("The match starts in {}", format_time(secs))),
+                            None => ui.colored_label(egui::Color32::GRAY, "Waiting for players"),
+                        };
+                    }
+                    MatchPhase::InProgress => {
+                        if let Some(secs) = state.time_left {
+                            ui.heading(egui::RichText::new(format_time(secs)).color(egui::Color32::WHITE));
+                        }
+                        match state.game_mode {
+                            GameMode::Deathmatch => {
+                                if let Some(score_limit) = state.score_limit {
+                                    ui.colored_label(egui::Color32::WHITE, format!("First to {score_limit} kills"));
+                                }
+                            }
+                            GameMode::TeamDeathmatch => {
+                                ui.horizontal(|ui| {
+                                    ui.colored_label(team_color(Team::Red), format!("Red {}", state.red_score));
+                                    ui.colored_label(egui::Color32::WHITE, "-");
+                                    ui.colored_label(team_color(Team::Blue), format!("{} Blue", state.blue_score));
+                                });
+                            }
+                            GameMode::Coop => {
+                                ui.colored_label(egui::Color32::WHITE, format!("Enemies left: {}", state.enemies_left));
+                            }
+                        }
+                    }
+                    MatchPhase::PostMatch => {
+                        let (text, color) = match state.result {
+                            Some(MatchResult::Winner(identity)) => (format!("{identity} wins!"), egui::Color32::YELLOW),
+                            Some(MatchResult::WinningTeam(team)) => (format!("{team:?} team wins!"), team_color(team)),
+                            Some(MatchResult::Victory) => ("Victory!".to_string(), egui::Color32::GREEN),
+                            Some(MatchResult::Defeat) => ("Defeat".to_string(), egui::Color32::RED),
+                            Some(MatchResult::Draw) | None => ("Draw".to_string(), egui::Color32::WHITE),
+                        };
+                        ui.heading(egui::RichText::new(text).color(color).size(32.0));
+                        if let Some(secs) = state.time_left {
+                            ui.colored_label(egui::Color32::WHITE, format!("Next match in {}", format_time(secs)));
+                        }
+                    }
+                }
+            });
+        });
+}
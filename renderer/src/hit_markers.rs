@@ -0,0 +1,124 @@
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use bevy_rich_text3d::{Text3d, Text3dStyling, TextAtlas};
+use shared::prelude::{DespawnAfter, HitConfirmed};
+
+use crate::hud::Crosshair;
+
+/// Shows the hits the server confirmed: a marker on the crosshair and the damage floating where the projectile landed
+pub(crate) struct HitMarkersPlugin;
+
+impl Plugin for HitMarkersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(hit_confirmed_system);
+        app.add_systems(Update, (
+            float_damage_numbers_system,
+            fade_hit_markers_system,
+        ));
+    }
+}
+
+/// How long (in seconds) the marker stays on the crosshair
+const HIT_MARKER_SECS: f32 = 0.25;
+/// How long (in seconds) the damage numbers float before disappearing
+const DAMAGE_NUMBER_SECS: f32 = 1.0;
+/// How fast (in m/s) the damage numbers float up
+const DAMAGE_NUMBER_RISE_SPEED: f32 = 1.5;
+/// The damage numbers are scaled with their distance to the camera so that they keep the same size on screen
+const DAMAGE_NUMBER_SCALE_PER_METER: f32 = 0.00015;
+
+const HIT_COLOR: LinearRgba = LinearRgba::new(1.0, 1.0, 1.0, 1.0);
+const KILL_COLOR: LinearRgba = LinearRgba::new(4.0, 0.2, 0.1, 1.0);
+
+#[derive(Component, Debug)]
+struct HitMarker;
+
+#[derive(Component, Debug)]
+struct DamageNumber;
+
+fn hit_confirmed_system(
+    trigger: Trigger<HitConfirmed>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    crosshair: Query<Entity, With<Crosshair>>,
+    markers: Query<Entity, With<HitMarker>>,
+) {
+    let hit = trigger.event();
+    let color = if hit.killed { KILL_COLOR } else { HIT_COLOR };
+
+    if let Ok(crosshair) = crosshair.single() {
+        // only one marker at a time, a new hit restarts it
+        for marker in markers.iter() {
+            commands.entity(marker).despawn();
+        }
+        commands.entity(crosshair).with_child((
+            HitMarker,
+            Text3d::new("X"),
+            Text3dStyling {
+                font: "Roboto".into(),
+                size: 64.0,
+                ..default()
+            },
+            Mesh3d::default(),
+            MeshMaterial3d(materials.add(text_material(color))),
+            // slightly in front of the crosshair
+            Transform::from_translation(Vec3::new(0.0, 0.0, 0.001))
+                .with_scale(Vec3::splat(if hit.killed { 0.0006 } else { 0.0004 })),
+            DespawnAfter(Timer::from_seconds(HIT_MARKER_SECS, TimerMode::Once)),
+            NotShadowCaster,
+        ));
+    }
+
+    commands.spawn((
+        DamageNumber,
+        Text3d::new(hit.damage.to_string()),
+        Text3dStyling {
+            font: "Roboto".into(),
+            size: if hit.killed { 96.0 } else { 64.0 },
+            ..default()
+        },
+        Mesh3d::default(),
+        MeshMaterial3d(materials.add(text_material(color))),
+        Transform::from_translation(hit.position),
+        DespawnAfter(Timer::from_seconds(DAMAGE_NUMBER_SECS, TimerMode::Once)),
+        NotShadowCaster,
+    ));
+}
+
+fn text_material(color: LinearRgba) -> StandardMaterial {
+    StandardMaterial {
+        base_color: color.into(),
+        emissive: color,
+        base_color_texture: Some(TextAtlas::DEFAULT_IMAGE.clone()),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    }
+}
+
+/// Rise and keep facing the camera
+fn float_damage_numbers_system(
+    time: Res<Time>,
+    camera: Query<&GlobalTransform, With<Camera3d>>,
+    mut damage_numbers: Query<&mut Transform, With<DamageNumber>>,
+) {
+    let Ok(camera) = camera.single() else { return };
+    for mut transform in damage_numbers.iter_mut() {
+        // there is no up in space, rise towards the top of the screen
+        transform.translation += camera.up() * DAMAGE_NUMBER_RISE_SPEED * time.delta_secs();
+        transform.rotation = camera.rotation();
+        let distance = camera.translation().distance(transform.translation);
+        transform.scale = Vec3::splat(distance * DAMAGE_NUMBER_SCALE_PER_METER);
+    }
+}
+
+fn fade_hit_markers_system(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    markers: Query<(&DespawnAfter, &MeshMaterial3d<StandardMaterial>), Or<(With<HitMarker>, With<DamageNumber>)>>,
+) {
+    for (despawn_after, material) in markers.iter() {
+        let Some(material) = materials.get_mut(material.id()) else { continue };
+        let alpha = 1.0 - despawn_after.0.fraction();
+        material.base_color.set_alpha(alpha);
+        material.emissive.alpha = alpha;
+    }
+}
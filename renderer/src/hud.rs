@@ -6,15 +6,24 @@ use bevy_rich_text3d::{LoadFonts, Text3d, Text3dPlugin, Text3dStyling, TextAtlas
 use lightyear::prediction::diagnostics::PredictionMetrics;
 use lightyear::prelude::*;
 use serde::Deserialize;
+use rand::Rng;
+use shared::energy::Energy;
+use shared::impacts::ShipImpactEvent;
+use shared::keys::{HeldKeys, KeyColor};
+use shared::prelude::Damageable;
+use shared::ships::{Ship, ShipsData};
 use shared::weapons::{CurrentWeaponIndex, WeaponInventory, WeaponsData};
 
 use shared::player::PlayerShip;
 
+use crate::game_mode::key_color;
+
 pub struct HudPlugin;
 
 impl Plugin for HudPlugin {
     fn build(&self, app: &mut App) {
         app.add_observer(spawn_2d_hud_system);
+        app.add_observer(add_screen_shake_system);
         app.init_resource::<CrosshairTextures>();
         app.add_plugins(ConfigAssetLoaderPlugin::<HudConfig>::new("data/hud.ron"));
         app.add_plugins(Text3dPlugin {
@@ -27,11 +36,20 @@ impl Plugin for HudPlugin {
             ..default()
         });
         app.add_plugins(FrameTimeDiagnosticsPlugin::default());
+        app.add_systems(PreUpdate, remove_screen_shake_system);
         app.add_systems(Update, (
             prediction_metrics_system,
             crosshair_system.run_if(resource_exists::<WeaponsData>),
             camera_sway_system.run_if(resource_exists::<HudConfig>),
-            update_stats_system.run_if(resource_exists::<WeaponsData>)
+            apply_screen_shake_system
+                .after(camera_sway_system)
+                .run_if(resource_exists::<HudConfig>),
+            update_stats_system.run_if(resource_exists::<WeaponsData>),
+            update_energy_system.run_if(resource_exists::<ShipsData>),
+            (
+                update_hud_system.run_if(resource_exists::<ShipsData>),
+                (hud_2d_system, hud_3d_system, key_icons_system),
+            ).chain(),
         ));
     }
 }
@@ -45,21 +63,41 @@ struct HudConfig {
     pub head_x_amount: f32,
     pub head_y_amount: f32,
     pub head_z_amount: f32,
+    /// How much a point of impact damage shakes the screen, the shake is capped at 1.0
+    pub screen_shake_per_damage: f32,
+    /// Rotation (in radians) of the camera at full shake
+    pub screen_shake_max_angle: f32,
+    /// How much shake goes away per second
+    pub screen_shake_decay: f32,
 }
 
-#[derive(Default, Component)]
+/// How much of the damage flash goes away per second
+const DAMAGE_FLASH_DECAY: f32 = 3.0;
+const HEALTH_BAR_COLOR: Color = Color::srgb(1.0, 0.5, 0.0);
+const ENERGY_BAR_COLOR: Color = Color::srgb(0.1, 0.5, 1.0);
+const DAMAGE_FLASH_COLOR: Color = Color::srgb(1.0, 0.1, 0.0);
+const HEALTH_TEXT_EMISSIVE: LinearRgba = LinearRgba::new(1.0, 0.5, 0.0, 1.0);
+const DAMAGE_FLASH_EMISSIVE: LinearRgba = LinearRgba::new(4.0, 0.2, 0.1, 1.0);
+const HEALTH_TEXT_SCALE: f32 = 0.0005;
+
+/// The state of our ship as shown by the 2D and 3D HUD, copied from the predicted ship every frame
+#[derive(Default, Component, Debug)]
 struct Hud {
-    pub health: i32,
-    pub red_key: bool,
-    pub blue_key: bool,
-    pub yellow_key: bool,
+    pub health: u16,
+    /// The starting health of the ship
+    pub max_health: u16,
+    /// Between 0.0 and 1.0, `None` if the ship has no energy pool
+    pub energy: Option<f32>,
+    pub keys: HeldKeys,
+    /// Between 0.0 and 1.0, set to 1.0 when the health drops and fading out
+    pub damage_flash: f32,
 }
 
 #[derive(Component)]
 struct PredictionMetricsText;
 
 #[derive(Component, Debug)]
-struct Crosshair;
+pub(crate) struct Crosshair;
 
 #[derive(Component, Debug)]
 struct HealthText;
@@ -67,12 +105,43 @@ struct HealthText;
 #[derive(Component, Debug)]
 struct AmmoText;
 
+#[derive(Component, Debug)]
+struct EnergyText;
+
+#[derive(Component, Debug)]
+struct HealthLabel;
+
+/// The filled part of the 2D health bar
+#[derive(Component, Debug)]
+struct HealthBar;
+
+/// The filled part of the 2D energy bar
+#[derive(Component, Debug)]
+struct EnergyBar;
+
+/// The 2D energy bar with its background, hidden for ships without energy
+#[derive(Component, Debug)]
+struct EnergyGauge;
+
+/// Shown in the 2D and 3D HUD while the ship holds the key
+#[derive(Component, Debug)]
+struct KeyIcon(KeyColor);
+
 
 #[derive(Resource, Debug, Default)]
 struct CrosshairTextures {
     pub textures: HashMap<String, Handle<Image>>,
 }
 
+/// Shakes the camera after an impact
+#[derive(Component, Default, Clone)]
+struct ScreenShake {
+    /// between 0.0 and 1.0, the shake is proportional to trauma squared so that small impacts are subtle
+    trauma: f32,
+    /// the rotation offset applied this frame, removed at the start of the next one
+    applied: Quat,
+}
+
 #[derive(Component, Default, Clone)]
 struct GForceData {
     prev_linear_velocity: Vec3,
@@ -107,15 +176,15 @@ fn spawn_2d_hud_system(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
     weapons_data: Res<WeaponsData>,
+    huds: Query<(), With<Hud>>,
 ) {
+    // the camera is spawned again with every ship
+    if !huds.is_empty() {
+        return;
+    }
     commands
         .spawn((
-            Hud {
-                health: 100,
-                red_key: false,
-                blue_key: false,
-                yellow_key: false,
-            },
+            Hud::default(),
             Node {
                 position_type: PositionType::Relative,
                 width: Val::Percent(100.0),
@@ -134,6 +203,82 @@ fn spawn_2d_hud_system(
                 },
                 Text::new("Prediction metrics..."),
             ));
+
+            // Health, energy and keys, at the bottom of the screen
+            parent.spawn(Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(20.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            }).with_children(|parent| {
+                parent.spawn(Node {
+                    width: Val::Px(260.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn((
+                        HealthLabel,
+                        Text::new("0 / 0"),
+                        TextFont::from_font_size(20.0),
+                        TextColor(HEALTH_BAR_COLOR),
+                    ));
+                    parent.spawn((
+                        Node {
+                            width: Val::Percent(100.0),
+                            height: Val::Px(12.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+                    )).with_child((
+                        HealthBar,
+                        Node {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(HEALTH_BAR_COLOR),
+                    ));
+                    parent.spawn((
+                        EnergyGauge,
+                        Node {
+                            width: Val::Percent(100.0),
+                            height: Val::Px(6.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+                        Visibility::Hidden,
+                    )).with_child((
+                        EnergyBar,
+                        Node {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(ENERGY_BAR_COLOR),
+                    ));
+                    parent.spawn(Node {
+                        column_gap: Val::Px(6.0),
+                        height: Val::Px(16.0),
+                        ..default()
+                    }).with_children(|parent| {
+                        for key in KeyColor::ALL {
+                            parent.spawn((
+                                KeyIcon(key),
+                                Node {
+                                    width: Val::Px(16.0),
+                                    height: Val::Px(16.0),
+                                    ..default()
+                                },
+                                BackgroundColor(key_color(key)),
+                                BorderRadius::all(Val::Px(3.0)),
+                                Visibility::Hidden,
+                            ));
+                        }
+                    });
+                });
+            });
         });
 }
 
@@ -176,6 +321,50 @@ pub fn spawn_3d_hud(
             }
         )),
         Transform::from_translation(Vec3::new(-0.25, -0.15, -0.25))
+            .with_scale(Vec3::splat(HEALTH_TEXT_SCALE))
+            .with_rotation(Quat::from_euler(EulerRot::XYZ, 0.0, 0.45, 0.0)),
+        NotShadowCaster,
+    ));
+
+    // the keys, in a row above the health
+    let key_mesh = meshes.add(Mesh::from(Rectangle::new(0.008, 0.008)));
+    for (i, key) in KeyColor::ALL.into_iter().enumerate() {
+        let color: LinearRgba = key_color(key).into();
+        ship.spawn((
+            KeyIcon(key),
+            Mesh3d(key_mesh.clone()),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: color.into(),
+                emissive: color,
+                unlit: true,
+                ..Default::default()
+            })),
+            Transform::from_translation(Vec3::new(-0.27 + i as f32 * 0.012, -0.125, -0.24))
+                .with_rotation(Quat::from_euler(EulerRot::XYZ, 0.0, 0.45, 0.0)),
+            Visibility::Hidden,
+            NotShadowCaster,
+        ));
+    }
+
+    ship.spawn((
+        EnergyText,
+        Text3d::new("100"),
+        Text3dStyling {
+            font: "Roboto".into(),
+            size: 48.0,
+            ..default()
+        },
+        Mesh3d::default(),
+        MeshMaterial3d(materials.add(
+            StandardMaterial {
+                base_color: Color::srgba(1.0, 5.0, 10.0, 1.0),
+                emissive: LinearRgba::new(0.1, 0.5, 1.0, 1.0),
+                base_color_texture: Some(TextAtlas::DEFAULT_IMAGE.clone()),
+                alpha_mode: AlphaMode::Blend,
+                ..Default::default()
+            }
+        )),
+        Transform::from_translation(Vec3::new(-0.25, -0.18, -0.25))
             .with_scale(Vec3::new(0.0005, 0.0005, 0.0005))
             .with_rotation(Quat::from_euler(EulerRot::XYZ, 0.0, 0.45, 0.0)),
         NotShadowCaster,
@@ -279,6 +468,63 @@ fn camera_sway_system(
     }
 }
 
+/// When our own ship slams into something, shake the camera
+fn add_screen_shake_system(
+    trigger: Trigger<ShipImpactEvent>,
+    config: Option<Res<HudConfig>>,
+    controlled_ships: Query<&Children, With<Controlled>>,
+    mut cameras: Query<Option<&mut ScreenShake>, With<Camera3d>>,
+    mut commands: Commands,
+) {
+    let Some(config) = config else { return };
+    let Ok(children) = controlled_ships.get(trigger.event().entity) else { return };
+    let trauma = trigger.event().damage as f32 * config.screen_shake_per_damage;
+    for child in children.iter() {
+        match cameras.get_mut(child) {
+            Ok(Some(mut shake)) => shake.trauma = (shake.trauma + trauma).min(1.0),
+            Ok(None) => {
+                commands.entity(child).insert(ScreenShake {
+                    trauma: trauma.min(1.0),
+                    applied: Quat::IDENTITY,
+                });
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Remove last frame's shake so that the camera sway works on the unshaken camera
+fn remove_screen_shake_system(
+    mut cameras: Query<(&mut Transform, &mut ScreenShake)>,
+) {
+    for (mut transform, mut shake) in cameras.iter_mut() {
+        transform.rotation = transform.rotation * shake.applied.inverse();
+        shake.applied = Quat::IDENTITY;
+    }
+}
+
+fn apply_screen_shake_system(
+    time: Res<Time>,
+    config: Res<HudConfig>,
+    mut cameras: Query<(&mut Transform, &mut ScreenShake)>,
+) {
+    let mut rng = rand::rng();
+    for (mut transform, mut shake) in cameras.iter_mut() {
+        if shake.trauma <= 0.0 {
+            continue;
+        }
+        let angle = config.screen_shake_max_angle * shake.trauma * shake.trauma;
+        shake.applied = Quat::from_euler(
+            EulerRot::XYZ,
+            rng.random_range(-angle..=angle),
+            rng.random_range(-angle..=angle),
+            rng.random_range(-angle..=angle),
+        );
+        transform.rotation = transform.rotation * shake.applied;
+        shake.trauma = (shake.trauma - config.screen_shake_decay * time.delta_secs()).max(0.0);
+    }
+}
+
 fn crosshair_system(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
@@ -322,3 +568,88 @@ fn update_stats_system(
         }
     }
 }
+
+fn update_energy_system(
+    ships_data: Res<ShipsData>,
+    controlled_player: Query<(&Energy, &Ship), (With<PlayerShip>, With<Predicted>)>,
+    mut energy_text: Query<&mut Text3d, With<EnergyText>>,
+) {
+    let Ok((energy, ship)) = controlled_player.single() else { return };
+    let Some(ship_data) = ships_data.ships.get(&ship.0) else { return };
+
+    if let Ok(mut energy_text) = energy_text.single_mut() {
+        let percent = (energy.fraction(&ship_data.energy) * 100.0).round() as u32;
+        *energy_text = Text3d::new(format!("{percent}%"));
+    }
+}
+
+/// Copy the state of our ship into the [`Hud`], and flash it when the health drops
+fn update_hud_system(
+    time: Res<Time>,
+    ships_data: Res<ShipsData>,
+    controlled_player: Query<(&Damageable, &Ship, Option<&Energy>, Option<&HeldKeys>), (With<PlayerShip>, With<Predicted>)>,
+    mut hud: Single<&mut Hud>,
+) {
+    hud.damage_flash = (hud.damage_flash - DAMAGE_FLASH_DECAY * time.delta_secs()).max(0.0);
+    // keep the last values while we are dead
+    let Ok((damageable, ship, energy, keys)) = controlled_player.single() else { return };
+    let Some(ship_data) = ships_data.ships.get(&ship.0) else { return };
+
+    if damageable.health < hud.health {
+        hud.damage_flash = 1.0;
+    }
+    hud.health = damageable.health;
+    hud.max_health = ship_data.starting_health;
+    hud.energy = energy.map(|energy| energy.fraction(&ship_data.energy));
+    hud.keys = keys.copied().unwrap_or_default();
+}
+
+fn hud_2d_system(
+    hud: Single<&Hud>,
+    mut health_label: Query<(&mut Text, &mut TextColor), With<HealthLabel>>,
+    mut health_bar: Query<(&mut Node, &mut BackgroundColor), With<HealthBar>>,
+    mut energy_bar: Query<&mut Node, (With<EnergyBar>, Without<HealthBar>)>,
+    mut energy_gauge: Query<&mut Visibility, With<EnergyGauge>>,
+) {
+    let health_fraction = hud.health as f32 / hud.max_health.max(1) as f32;
+    let health_color = HEALTH_BAR_COLOR.mix(&DAMAGE_FLASH_COLOR, hud.damage_flash);
+
+    if let Ok((mut text, mut color)) = health_label.single_mut() {
+        text.0 = format!("{} / {}", hud.health, hud.max_health);
+        color.0 = health_color;
+    }
+    if let Ok((mut node, mut color)) = health_bar.single_mut() {
+        node.width = Val::Percent(health_fraction.min(1.0) * 100.0);
+        color.0 = health_color;
+    }
+    if let Ok(mut visibility) = energy_gauge.single_mut() {
+        *visibility = if hud.energy.is_some() { Visibility::Inherited } else { Visibility::Hidden };
+    }
+    if let (Ok(mut node), Some(energy)) = (energy_bar.single_mut(), hud.energy) {
+        node.width = Val::Percent(energy * 100.0);
+    }
+}
+
+fn hud_3d_system(
+    hud: Single<&Hud>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut health_text: Query<(&mut Text3d, &mut Transform, &MeshMaterial3d<StandardMaterial>), With<HealthText>>,
+) {
+    let Ok((mut text, mut transform, material)) = health_text.single_mut() else { return };
+    *text = Text3d::new(format!("{}/{}", hud.health, hud.max_health));
+    // grow a bit and glow red when hit
+    transform.scale = Vec3::splat(HEALTH_TEXT_SCALE * (1.0 + 0.3 * hud.damage_flash));
+    if let Some(material) = materials.get_mut(material.id()) {
+        material.emissive = HEALTH_TEXT_EMISSIVE.mix(&DAMAGE_FLASH_EMISSIVE, hud.damage_flash);
+    }
+}
+
+/// Show the keys we hold, in both the 2D and 3D HUD
+fn key_icons_system(
+    hud: Single<&Hud>,
+    mut key_icons: Query<(&KeyIcon, &mut Visibility)>,
+) {
+    for (key_icon, mut visibility) in key_icons.iter_mut() {
+        *visibility = if hud.keys.has(key_icon.0) { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
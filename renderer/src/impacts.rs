@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use bevy_config_stack::prelude::ConfigAssetLoadedEvent;
+use lightyear::prelude::*;
+use sfx::prelude::kira::track::SpatialTrackDistances;
+use sfx::prelude::kira::{Decibels, Easing, Mapping, Mix, Value};
+use sfx::prelude::{LowPassSettings, ReverbSettings, SfxEmitter};
+use sfx::SfxManager;
+use shared::prelude::{ReverbMix, ShipImpactEvent};
+use shared::ships::{Ship, ShipsData};
+
+/// Plays a crunch sound when a ship slams into something
+pub(crate) struct ImpactsPlugin;
+
+impl Plugin for ImpactsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(ship_impact_sound_system);
+        app.add_systems(Update, load_impact_sounds_system.run_if(resource_exists::<ShipsData>));
+    }
+}
+
+/// Whenever ship data is loaded/reloaded we load/reload the impact sounds
+fn load_impact_sounds_system(
+    mut sfx_manager: ResMut<SfxManager>,
+    asset_server: Res<AssetServer>,
+    ships_data: Res<ShipsData>,
+    mut events: EventReader<ConfigAssetLoadedEvent<ShipsData>>,
+) {
+    for _ in events.read() {
+        for ship in ships_data.ships.values() {
+            if let Some(impact_sound) = &ship.impact_sound {
+                // We treat the sound path as the unique id.
+                let path = impact_sound.compute_asset_path();
+                sfx_manager.load_sfx(path.clone(), path, &asset_server);
+            }
+        }
+    }
+}
+
+fn ship_impact_sound_system(
+    trigger: Trigger<ShipImpactEvent>,
+    ships_data: Option<Res<ShipsData>>,
+    mut commands: Commands,
+    ships: Query<(&Ship, Has<Controlled>)>,
+) {
+    let event = trigger.event();
+    let Some(ships_data) = ships_data else { return };
+    let Ok((ship, is_controlled)) = ships.get(event.entity) else { return };
+    let Some(impact_sound) = ships_data.ships.get(&ship.0).and_then(|ship| ship.impact_sound.as_ref()) else { return };
+
+    commands.spawn((
+        SfxEmitter {
+            asset_unique_id: impact_sound.compute_asset_path(),
+            // our own impacts are heard from inside the cockpit
+            spatial: if is_controlled {
+                None
+            } else {
+                Some(SpatialTrackDistances {
+                    min_distance: impact_sound.min_distance,
+                    max_distance: impact_sound.max_distance,
+                })
+            },
+            reverb: impact_sound.reverb.as_ref().map(|reverb| ReverbSettings {
+                damping: reverb.damping as f64,
+                feedback: reverb.feedback as f64,
+                mix: if reverb.mix == ReverbMix::Wet { Mix::WET } else { Mix::DRY },
+                volume: Value::Fixed(Decibels(1.0)),
+            }),
+            low_pass: if is_controlled {
+                None
+            } else {
+                impact_sound.distance_muffle.as_ref().map(|distance_muffle| LowPassSettings {
+                    cutoff_hz: Value::FromListenerDistance(Mapping {
+                        input_range: (distance_muffle.min_distance as f64, distance_muffle.max_distance as f64),
+                        output_range: (20000.0, distance_muffle.cutoff_hz as f64),
+                        easing: Easing::Linear,
+                    })
+                })
+            },
+            doppler_enabled: false,
+            speed_of_sound: impact_sound.speed_of_sound as f64,
+            volume: Value::Fixed(Decibels(impact_sound.volume_db)),
+            despawn_entity_after_secs: impact_sound.despawn_delay,
+            ..default()
+        },
+        Transform::from_translation(event.position),
+    ));
+}
@@ -2,9 +2,19 @@ mod player;
 mod weapons;
 mod bot;
 mod audio;
+mod impacts;
+mod game_mode;
 
 #[cfg(feature = "client")]
 mod hud;
+#[cfg(feature = "client")]
+mod chat;
+#[cfg(feature = "client")]
+mod hit_markers;
+#[cfg(feature = "client")]
+mod damage_indicators;
+#[cfg(feature = "client")]
+mod scoreboard;
 
 mod physics;
 
@@ -38,6 +48,8 @@ impl Plugin for RendererPlugin {
         app.add_plugins(bot::BotPlugin);
         app.add_plugins(physics::PhysicsPlugin);
         app.add_plugins(player::PlayerPlugin);
+        app.add_plugins(impacts::ImpactsPlugin);
+        app.add_plugins(game_mode::GameModePlugin);
         app.add_plugins(sfx::SfxAudioPlugin::default());
         app.add_plugins(weapons::WeaponsPlugin);
         app.add_plugins(vfx::VfxPlugin);
@@ -46,6 +58,10 @@ impl Plugin for RendererPlugin {
         {
             app.add_plugins(FrameInterpolationPlugin::<Transform>::default());
             app.add_plugins(hud::HudPlugin);
+            app.add_plugins(chat::ChatBoxPlugin);
+            app.add_plugins(hit_markers::HitMarkersPlugin);
+            app.add_plugins(damage_indicators::DamageIndicatorsPlugin);
+            app.add_plugins(scoreboard::ScoreboardPlugin);
         }
 
         // SYSTEMS
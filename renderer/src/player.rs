@@ -3,10 +3,12 @@ use sfx::prelude::SfxListener;
 use bevy::color::palettes::basic::BLUE;
 use bevy::prelude::*;
 use bevy::window::{CursorGrabMode, CursorOptions, PrimaryWindow};
+use leafwing_input_manager::prelude::ActionState;
 use lightyear::frame_interpolation::FrameInterpolate;
 use lightyear::prelude::*;
 use lightyear::prelude::PredictionSet;
 use shared::player::PlayerShip;
+use shared::prelude::PlayerInput;
 use shared::weapons::WeaponsData;
 #[cfg(feature = "client")]
 use crate::hud::spawn_3d_hud;
@@ -29,11 +31,12 @@ impl Plugin for PlayerPlugin {
 }
 
 fn toggle_mouse_pointer_system(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    // the client keeps the state of the HUD actions in a resource
+    hud_actions: Option<Res<ActionState<PlayerInput>>>,
     mut windows: Single<&mut Window, With<PrimaryWindow>>,
 ) {
     let mut window = windows.into_inner();
-    if keyboard_input.just_pressed(KeyCode::Tab) {
+    if hud_actions.is_some_and(|actions| actions.just_pressed(&PlayerInput::ToggleMousePointer)) {
         toggle_mouse_pointer(window.as_mut());
     }
 }
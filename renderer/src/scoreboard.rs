@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
+use leafwing_input_manager::prelude::ActionState;
+use lightyear::prelude::*;
+use shared::prelude::{AppState, KillFeedEntry, MatchPhase, MatchState, PlayerInput, PlayingSubState, Scoreboard, UniqueIdentity, WeaponsData};
+
+use crate::game_mode::team_color;
+
+/// The kill feed in the top right corner of the screen, and the scoreboard shown while
+/// [`PlayerInput::ShowScoreboard`] is held or the match is over
+pub(crate) struct ScoreboardPlugin;
+
+impl Plugin for ScoreboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KillFeed>();
+        app.add_observer(kill_feed_entry_received);
+        app.add_systems(OnEnter(AppState::TitleScreen), clear_kill_feed_system);
+        app.add_systems(Update, (
+            kill_feed_system,
+            scoreboard_system.run_if(in_state(AppState::Playing(PlayingSubState::Playing))),
+        ));
+    }
+}
+
+/// How many kills the feed shows at once
+const KILL_FEED_LEN: usize = 5;
+/// How long (in seconds) a kill stays in the feed
+const KILL_FEED_SECS: f32 = 6.0;
+
+#[derive(Resource, Default)]
+struct KillFeed {
+    /// The entries with the time they arrived, in seconds since startup
+    entries: VecDeque<(KillFeedEntry, f32)>,
+}
+
+fn kill_feed_entry_received(
+    trigger: Trigger<KillFeedEntry>,
+    time: Res<Time<Real>>,
+    mut kill_feed: ResMut<KillFeed>,
+) {
+    kill_feed.entries.push_back((trigger.event().clone(), time.elapsed_secs()));
+    while kill_feed.entries.len() > KILL_FEED_LEN {
+        kill_feed.entries.pop_front();
+    }
+}
+
+/// Forget the kills of the previous game
+fn clear_kill_feed_system(mut kill_feed: ResMut<KillFeed>) {
+    *kill_feed = KillFeed::default();
+}
+
+fn kill_feed_system(
+    mut ctx: EguiContexts,
+    time: Res<Time<Real>>,
+    weapons_data: Option<Res<WeaponsData>>,
+    mut kill_feed: ResMut<KillFeed>,
+) {
+    let now = time.elapsed_secs();
+    kill_feed.entries.retain(|(_, received_at)| now - received_at < KILL_FEED_SECS);
+    if kill_feed.entries.is_empty() {
+        return;
+    }
+
+    let ctx = ctx.ctx_mut();
+    egui::Area::new(egui::Id::new("kill_feed"))
+        // below the prediction metrics
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 90.0))
+        .show(ctx, |ui| {
+            for (entry, _) in kill_feed.entries.iter() {
+                let text = match entry.killer {
+                    Some(killer) if killer == entry.victim => format!("{} shot themselves down", entry.victim),
+                    Some(killer) => {
+                        let weapon = entry.weapon_index
+                            .and_then(|index| weapons_data.as_ref()?.weapons.get(&index).map(|weapon| weapon.name.as_str()))
+                            .unwrap_or("?");
+                        format!("{killer} [{weapon}] {}", entry.victim)
+                    }
+                    None => format!("{} crashed", entry.victim),
+                };
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                    ui.colored_label(egui::Color32::WHITE, text);
+                });
+            }
+        });
+}
+
+fn scoreboard_system(
+    mut ctx: EguiContexts,
+    // the client keeps the state of the HUD actions in a resource
+    hud_actions: Option<Res<ActionState<PlayerInput>>>,
+    scoreboard: Query<&Scoreboard>,
+    match_state: Query<&MatchState>,
+    local_id: Query<&LocalId, With<Client>>,
+) {
+    let held = hud_actions.is_some_and(|actions| actions.pressed(&PlayerInput::ShowScoreboard));
+    let match_over = match_state.single().is_ok_and(|state| state.phase == MatchPhase::PostMatch);
+    if !held && !match_over {
+        return;
+    }
+    let Ok(scoreboard) = scoreboard.single() else { return };
+    let has_teams = scoreboard.scores.iter().any(|(_, score)| score.team.is_some());
+    let me = local_id.single().ok().map(|local_id| UniqueIdentity::Player(local_id.0));
+
+    let ctx = ctx.ctx_mut();
+    egui::Area::new(egui::Id::new("scoreboard"))
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.heading("Scoreboard");
+                egui::Grid::new("scoreboard_grid")
+                    .striped(true)
+                    .min_col_width(60.0)
+                    .show(ui, |ui| {
+                        if has_teams {
+                            ui.strong("Team");
+                        }
+                        for header in ["Name", "Kills", "Deaths", "Assists", "Damage", "Accuracy"] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+
+                        for (identity, score) in scoreboard.ranked() {
+                            let color = if Some(*identity) == me { egui::Color32::YELLOW } else { egui::Color32::WHITE };
+                            if has_teams {
+                                match score.team {
+                                    Some(team) => ui.colored_label(team_color(team), format!("{team:?}")),
+                                    None => ui.label(""),
+                                };
+                            }
+                            ui.colored_label(color, identity.to_string());
+                            ui.colored_label(color, score.kills.to_string());
+                            ui.colored_label(color, score.deaths.to_string());
+                            ui.colored_label(color, score.assists.to_string());
+                            ui.colored_label(color, score.damage_dealt.to_string());
+                            ui.colored_label(color, format!("{:.0}%", score.accuracy() * 100.0));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+}
@@ -1,11 +1,18 @@
+use core::time::Duration;
+
 use avian3d::prelude::*;
 use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
 use lightyear::prelude::*;
+use lightyear::prelude::server::{ClientOf, Started};
 use lightyear_avian3d::prelude::LagCompensationHistory;
-use shared::bot::{BotShip, BotAttackKind};
-use shared::player::PlayerShip;
-use shared::prelude::{Damageable, GameLayer, UniqueIdentity};
-use shared::ships::{get_shared_ship_components, move_ship, Ship, ShipsData};
+use shared::bot::{BotShip, BotAttackKind, BotDifficulty, BotDifficultyProfile};
+use shared::player::{PlayerShip, ServerControlled};
+use shared::prelude::{Damageable, Energy, GameLayer, GameMode, PlayerInput, ProjectileHitEvent, Team, UniqueIdentity, WeaponFiredEvent, WeaponsSet};
+use shared::ships::{get_shared_ship_components, move_ship, FlightMode, Ship, ShipBehavior, ShipColliders, ShipId, ShipsData};
+use shared::weapons::{CurrentWeaponIndex, WeaponInventory, WeaponsData};
+use crate::map::SpawnPoints;
+use crate::squad::SquadMember;
 // TODO: should bots be handled similarly to players? i.e. they share most of the same code (visuals, collisions)
 //  but they are simply controlled by the server. The server could be sending fake inputs to the bots so that their movement
 //  is the same as players
@@ -14,22 +21,134 @@ use shared::ships::{get_shared_ship_components, move_ship, Ship, ShipsData};
 pub(crate) struct BotPlugin;
 impl Plugin for BotPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(BotManager { next_bot_id: 0 });
-        app.add_systems(Startup, spawn_bot);
+        app.init_resource::<BotSettings>();
+        app.init_resource::<HeardSounds>();
+        app.add_observer(hear_weapon_fired);
+        app.add_systems(Update, manage_bots_system
+            // in host-server mode the server only starts once the player hosts a game
+            .run_if(any_with_component::<Started>)
+            .run_if(resource_exists::<ShipsData>)
+            .run_if(resource_exists::<WeaponsData>)
+            // the co-op enemies are placed by the map
+            .run_if(|game_mode: Res<GameMode>| *game_mode != GameMode::Coop)
+        );
+        app.configure_sets(FixedUpdate, (
+            BotSet::Perceive,
+            BotSet::Move,
+            BotSet::Aim,
+        ).chain().before(WeaponsSet::Shoot).run_if(resource_exists::<ShipsData>));
         app.add_systems(FixedUpdate, (
-            target_tracking_system,
-            move_system.run_if(resource_exists::<ShipsData>),
+            (hearing_system, target_tracking_system).chain().in_set(BotSet::Perceive),
+            move_system.in_set(BotSet::Move),
+            aim_system.in_set(BotSet::Aim),
         ));
     }
 }
 
-#[derive(Resource)]
-struct BotManager {
-    next_bot_id: u32,
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum BotSet {
+    /// Bots look and listen for their targets
+    Perceive,
+    /// Bots move around their targets
+    Move,
+    /// Bots aim and shoot at their targets
+    Aim,
+}
+
+/// Controls how many bots are in the game and how good they are.
+/// Can be modified at runtime, bots are added or removed to match the settings.
+#[derive(Resource, Clone, Debug)]
+pub struct BotSettings {
+    /// the minimum number of bots in the game
+    pub bot_count: usize,
+    /// if set, bots are added until the number of players + bots reaches this number,
+    /// and removed again as players join
+    pub fill_player_slots: Option<usize>,
+    /// the difficulty of newly spawned bots
+    pub difficulty: BotDifficulty,
+    /// the ship newly spawned bots will fly
+    pub ship_id: ShipId,
+    /// if true, a bot takes over the ship of a player that disconnects
+    pub possess_disconnected: bool,
+    /// how long a possessed ship waits for its player to reconnect before it is removed
+    pub reconnect_grace_period: Duration,
+    /// if true, bots shoot at their targets, otherwise they only chase them
+    pub shooting: bool,
+}
+
+impl Default for BotSettings {
+    fn default() -> Self {
+        Self {
+            bot_count: 1,
+            fill_player_slots: None,
+            difficulty: BotDifficulty::default(),
+            ship_id: 1,
+            possess_disconnected: false,
+            reconnect_grace_period: Duration::from_secs(30),
+            shooting: true,
+        }
+    }
+}
+
+impl BotSettings {
+    /// the number of bots we want given the number of connected players
+    fn desired_bots(&self, player_count: usize) -> usize {
+        let fill = self.fill_player_slots
+            .map(|slots| slots.saturating_sub(player_count))
+            .unwrap_or(0);
+        self.bot_count.max(fill)
+    }
+}
+
+/// The AI that flies a ship. Every bot has one, and so does a player ship while a bot possesses it.
+#[derive(Component, Default)]
+#[require(BotPerception, BotSkill)]
+pub(crate) struct BotBrain {
+    /// the direction the bot wishes to move in, blended over time
+    pub(crate) wish_dir: Vec3,
+}
+
+/// The difficulty profile the bot was spawned with.
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct BotSkill(pub(crate) BotDifficultyProfile);
+
+impl Default for BotSkill {
+    fn default() -> Self {
+        Self(BotDifficulty::default().profile())
+    }
+}
+
+/// What a bot currently knows about its target.
+#[derive(Component, Default)]
+pub(crate) struct BotPerception {
+    pub(crate) memory: Option<BotMemory>,
+}
+
+pub(crate) struct BotMemory {
+    /// the entity the bot is after
+    pub(crate) entity: Entity,
+    /// the last position where the bot saw or heard the target, this is where it will search when it loses sight
+    pub(crate) last_known_position: Vec3,
+    /// whether the target is currently in sight
+    pub(crate) in_sight: bool,
+    /// the time in seconds since the target was last seen or heard
+    pub(crate) time_since_seen: f32,
 }
 
+/// A noise that bots can hear, e.g. a weapon firing or a projectile hitting something.
+struct HeardSound {
+    /// the entity that made the noise, the bot will go after this entity
+    source: Entity,
+    /// where the noise happened
+    position: Vec3,
+}
+
+/// Sounds that happened since the last time the bots listened.
+#[derive(Resource, Default)]
+struct HeardSounds(Vec<HeardSound>);
+
 #[derive(Component)]
-enum OrbitKind {
+pub(crate) enum OrbitKind {
     HorizontalClockwise,
     HorizontalCounterClockwise,
     VerticalClockwise,
@@ -37,14 +156,16 @@ enum OrbitKind {
 }
 
 #[derive(Component)]
-struct BotTarget {
+pub(crate) struct BotTarget {
     entity: Entity,
     orbit_kind: OrbitKind,
     orbit_timer: f32,
+    /// the time since the target was acquired, the bot only starts shooting once its reaction time has passed
+    reaction_timer: f32,
 }
 
 impl BotTarget {
-    fn new(entity: Entity) -> Self {
+    pub(crate) fn new(entity: Entity) -> Self {
         let orbit_kind = match rand::random::<u8>() % 4 {
             0 => OrbitKind::HorizontalClockwise,
             1 => OrbitKind::HorizontalCounterClockwise,
@@ -56,6 +177,7 @@ impl BotTarget {
             entity,
             orbit_kind,
             orbit_timer: 0.0,
+            reaction_timer: 0.0,
         }
     }
 
@@ -67,6 +189,10 @@ impl BotTarget {
         }
     }
 
+    fn clockwise(&self) -> bool {
+        matches!(self.orbit_kind, OrbitKind::HorizontalClockwise | OrbitKind::VerticalClockwise)
+    }
+
     fn choose_new_orbit_direction(&mut self) {
         self.orbit_kind = match rand::random::<u8>() % 4 {
             0 => OrbitKind::HorizontalClockwise,
@@ -77,10 +203,84 @@ impl BotTarget {
     }
 }
 
-fn spawn_bot(mut commands: Commands, mut bot_manager: ResMut<BotManager>) {
-    // TODO: use spawn-events so we can control spawn position, etc.
-    let position = Position(Vec3::new(1.0, 4.0, -1.0));
-    let rotation = Rotation(Quat::from_rotation_arc(Vec3::Y, Vec3::NEG_Z));
+/// Add or remove bots so that the number of bots matches the [`BotSettings`].
+fn manage_bots_system(
+    mut commands: Commands,
+    settings: Res<BotSettings>,
+    ships_data: Res<ShipsData>,
+    ship_colliders: Res<ShipColliders>,
+    weapons_data: Res<WeaponsData>,
+    players: Query<(), (With<ClientOf>, With<Connected>)>,
+    bots: Query<(Entity, &UniqueIdentity), With<BotShip>>,
+    spawn_points: SpawnPoints,
+) {
+    let desired_bots = settings.desired_bots(players.iter().count());
+    let current_bots = bots.iter().count();
+
+    if current_bots < desired_bots {
+        let Some(ship_data) = ships_data.ships.get(&settings.ship_id) else {
+            error!("Ship data not found for bot ship id: {}", settings.ship_id);
+            return;
+        };
+        // the ship's model might still be loading, we will try again next frame
+        let Some(collider) = ship_colliders.get(settings.ship_id) else { return };
+        // a bot that was shot down comes back with the same id, so that its score follows it
+        let mut bot_ids = bots.iter()
+            .filter_map(|(_, identity)| match identity {
+                UniqueIdentity::Bot(bot_id) => Some(*bot_id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut spawned = Vec::new();
+        for _ in current_bots..desired_bots {
+            let bot_id = (0..).find(|bot_id| !bot_ids.contains(bot_id)).unwrap();
+            bot_ids.push(bot_id);
+            // on maps without spawn points, the bots spawn where they always did
+            let (position, rotation) = spawn_points.pick(&spawned, (Vec3::new(1.0, 4.0, -1.0), Quat::from_rotation_arc(Vec3::Y, Vec3::NEG_Z)));
+            spawned.push(position);
+            let spawn = BotSpawn {
+                bot_id,
+                ship_id: settings.ship_id,
+                difficulty: settings.difficulty,
+                position,
+                rotation,
+            };
+            spawn_bot(&mut commands, spawn, ship_data, collider.clone(), &weapons_data);
+        }
+    } else if current_bots > desired_bots {
+        // remove the most recently added bots first
+        let mut bots = bots.iter()
+            .filter_map(|(entity, identity)| match identity {
+                UniqueIdentity::Bot(bot_id) => Some((*bot_id, entity)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        bots.sort_by_key(|(bot_id, _)| core::cmp::Reverse(*bot_id));
+        for (bot_id, entity) in bots.into_iter().take(current_bots - desired_bots) {
+            info!("Removing bot {}", bot_id);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Who a new bot is, what it flies and where it appears
+pub(crate) struct BotSpawn {
+    pub(crate) bot_id: u32,
+    pub(crate) ship_id: ShipId,
+    pub(crate) difficulty: BotDifficulty,
+    pub(crate) position: Vec3,
+    pub(crate) rotation: Quat,
+}
+
+pub(crate) fn spawn_bot(
+    commands: &mut Commands,
+    spawn: BotSpawn,
+    ship_data: &ShipBehavior,
+    collider: Collider,
+    weapons_data: &WeaponsData,
+) -> Entity {
+    let BotSpawn { bot_id, ship_id, difficulty, position, rotation } = spawn;
+    info!("Spawning bot {} ({:?})", bot_id, difficulty);
     commands.spawn(
         (
             Name::from("Bot"),
@@ -88,23 +288,27 @@ fn spawn_bot(mut commands: Commands, mut bot_manager: ResMut<BotManager>) {
             InterpolationTarget::to_clients(NetworkTarget::All),
             // in case the renderer is enabled on the server, we don't want the visuals to be replicated!
             DisableReplicateHierarchy,
-            UniqueIdentity::Bot(bot_manager.next_bot_id),
-            BotShip {
-                wish_dir: Vec3::ZERO,
-            },
+            UniqueIdentity::Bot(bot_id),
+            BotShip,
+            BotBrain::default(),
             Damageable {
-                health: 50,
+                health: ship_data.starting_health,
             },
-            Ship(1),
+            Energy::new(&ship_data.energy),
+            Ship(ship_id),
+            BotSkill(difficulty.profile()),
+            // bots shoot through the same code path as players, by pressing the buttons of their own ActionState
+            ActionState::<PlayerInput>::default(),
+            CurrentWeaponIndex(0),
+            WeaponInventory::from_data(weapons_data, ship_data.loadout.clone()),
             // TODO: UNDERSTAND WHY IT IS NECESSARY TO MANUALLY INSERT THE CORRECT POSITION/ROTATION
             //  ON THE ENTITY! I THOUGHT THE PREPARE_SET WOULD DO THIS AUTOMATICALLY
-            position,
-            rotation,
-            get_shared_ship_components(Collider::sphere(0.5)),
+            Position(position),
+            Rotation(rotation),
+            get_shared_ship_components(collider, &ship_data.physics),
             LagCompensationHistory::default(),
         )
-    );
-    bot_manager.next_bot_id += 1;
+    ).id()
 }
 
 /// The main bot movement system, this dictates how bots go after their target and navigate around the map.
@@ -120,19 +324,25 @@ fn move_system(
     spatial_query: SpatialQuery,
     fixed_time: Res<Time<Fixed>>,
     mut targets: Query<&mut BotTarget>,
-    positions: Query<&Position>,
-    mut bots: Query<(Entity, &Position, &mut LinearVelocity, &mut AngularVelocity, &Ship, &mut BotShip)>,
+    mut bots: Query<(Entity, &Position, &mut LinearVelocity, &mut AngularVelocity, &Ship, &mut BotBrain, &BotPerception, &BotSkill, Option<&SquadMember>)>,
     ships_data: Res<ShipsData>,
 ) {
     let delta = fixed_time.delta_secs();
 
-    for (bot_entity, bot_position, mut linear_velocity, mut angular_velocity, ship_index, mut bot) in bots.iter_mut() {
+    for (bot_entity, bot_position, mut linear_velocity, mut angular_velocity, ship_index, mut bot, perception, skill, squad_member) in bots.iter_mut() {
         if let Some(ship_behavior) = ships_data.ships.get(&ship_index.0) {
+            let skill = skill.0;
+            // squad members keep a distance that depends on their role
+            let role_distance_factor = squad_member.map_or(1.0, |member| member.role.distance_factor());
+            let back_off_distance = skill.scale_distance(ship_behavior.bot_behavior.back_off_distance) * role_distance_factor;
             let mut wish_dir = Vec3::ZERO;
             let mut found_bot_target = None;
             if let Ok(mut bot_target) = targets.get_mut(bot_entity) {
-                if let (Ok(target_position), Ok(bot_position)) = (positions.get(bot_target.entity), positions.get(bot_entity)) {
-                    let target_pos = target_position.0;
+                // the target is out of sight, head straight for the last place we knew it was
+                if let Some(memory) = perception.memory.as_ref().filter(|memory| !memory.in_sight) {
+                    wish_dir = (memory.last_known_position - bot_position.0).normalize_or_zero();
+                } else if let Some(memory) = perception.memory.as_ref() {
+                    let target_pos = memory.last_known_position;
                     let bot_pos = bot_position.0;
                     let distance = target_pos.distance(bot_pos);
                     let dir_to_target = (target_pos - bot_pos).normalize_or_zero();
@@ -146,35 +356,40 @@ fn move_system(
                             orbit_dir_target_blend_amount,
                             wish_dir_random_factor
                         } => {
-                            bot_target.update(delta, change_orbit_dir_interval);
+                            let target_distance = skill.scale_distance(target_distance) * role_distance_factor;
+                            bot_target.update(delta, skill.scale_interval(change_orbit_dir_interval));
 
                             let to_target = target_pos - bot_pos;
 
-                            let orbit_dir = match bot_target.orbit_kind {
-                                OrbitKind::HorizontalClockwise => {
-                                    let to_target_flat = Vec3::new(to_target.x, 0.0, to_target.z);
-                                    Vec3::new(-to_target_flat.z, 0.0, to_target_flat.x)
-                                }
-                                OrbitKind::HorizontalCounterClockwise => {
-                                    let to_target_flat = Vec3::new(to_target.x, 0.0, to_target.z);
-                                    Vec3::new(to_target_flat.z, 0.0, -to_target_flat.x)
-                                }
-                                OrbitKind::VerticalClockwise => {
-                                    let to_target_vertical = Vec3::new(to_target.x, to_target.y, 0.0);
-                                    Vec3::new(-to_target_vertical.y, to_target_vertical.x, 0.0)
-                                }
-                                OrbitKind::VerticalCounterClockwise => {
-                                    let to_target_vertical = Vec3::new(to_target.x, to_target.y, 0.0);
-                                    Vec3::new(to_target_vertical.y, -to_target_vertical.x, 0.0)
-                                }
-                            }.normalize();
+                            // squad members orbit in different planes so they don't end up in each other's way
+                            let orbit_dir = match squad_member {
+                                Some(member) => member.orbit_dir(to_target, bot_target.clockwise()),
+                                None => match bot_target.orbit_kind {
+                                    OrbitKind::HorizontalClockwise => {
+                                        let to_target_flat = Vec3::new(to_target.x, 0.0, to_target.z);
+                                        Vec3::new(-to_target_flat.z, 0.0, to_target_flat.x)
+                                    }
+                                    OrbitKind::HorizontalCounterClockwise => {
+                                        let to_target_flat = Vec3::new(to_target.x, 0.0, to_target.z);
+                                        Vec3::new(to_target_flat.z, 0.0, -to_target_flat.x)
+                                    }
+                                    OrbitKind::VerticalClockwise => {
+                                        let to_target_vertical = Vec3::new(to_target.x, to_target.y, 0.0);
+                                        Vec3::new(-to_target_vertical.y, to_target_vertical.x, 0.0)
+                                    }
+                                    OrbitKind::VerticalCounterClockwise => {
+                                        let to_target_vertical = Vec3::new(to_target.x, to_target.y, 0.0);
+                                        Vec3::new(to_target_vertical.y, -to_target_vertical.x, 0.0)
+                                    }
+                                }.normalize(),
+                            };
 
                             // If too far, blend in some inward movement
                             if distance > target_distance {
                                 wish_dir = ((orbit_dir * orbit_dir_blend_amount) + dir_to_target).normalize();
                             }
                             // If too close, blend in some outward movement
-                            else if distance < ship_behavior.bot_behavior.back_off_distance {
+                            else if distance < back_off_distance {
                                 wish_dir = ((orbit_dir * orbit_dir_back_off_blend_amount) - dir_to_target).normalize();
                             }
                             // Otherwise pure orbital motion with a slight bias towards the target
@@ -190,9 +405,10 @@ fn move_system(
                             );
                         }
                         BotAttackKind::Standard { target_distance, .. } => {
+                            let target_distance = skill.scale_distance(target_distance) * role_distance_factor;
                             if distance > target_distance {
                                 wish_dir = dir_to_target;
-                            } else if distance < ship_behavior.bot_behavior.back_off_distance {
+                            } else if distance < back_off_distance {
                                 wish_dir = -dir_to_target;
                             }
                         }
@@ -227,32 +443,196 @@ fn move_system(
             // a lower wish_dir_change_speed will make the bot change direction slower
             bot.wish_dir = bot.wish_dir.lerp(wish_dir, ship_behavior.bot_behavior.wish_dir_change_speed * delta);
 
-            move_ship(&fixed_time, &ship_behavior, &mut linear_velocity, &mut angular_velocity, bot.wish_dir, None);
+            // the blended direction is shorter while turning, but bots always fly at full throttle
+            move_ship(&fixed_time, &ship_behavior, &mut linear_velocity, &mut angular_velocity, bot.wish_dir.normalize_or_zero(), FlightMode::Assisted, None);
+        }
+    }
+}
+
+/// How fast the bots turn towards where they aim, the angle left to turn is corrected at this rate per second
+const BOT_TURN_RATE: f32 = 8.0;
+
+/// Unless [`BotSettings::shooting`] is disabled, turn the bots towards the target they can see,
+/// and pull the trigger once they had time to react.
+/// The aim is off by a random amount that depends on the accuracy of the bot.
+///
+/// The bots turn through their angular velocity so that the physics rotate the ship,
+/// and the actual shooting is handled by the same system as for players, which reads the bot's [`ActionState`].
+fn aim_system(
+    fixed_time: Res<Time<Fixed>>,
+    bot_settings: Res<BotSettings>,
+    ships_data: Res<ShipsData>,
+    mut targets: Query<&mut BotTarget>,
+    mut bots: Query<(Entity, &Position, &Rotation, &mut AngularVelocity, &Ship, &BotPerception, &BotSkill, &mut ActionState<PlayerInput>, Option<&SquadMember>), With<BotBrain>>,
+) {
+    let delta = fixed_time.delta_secs();
+    let elapsed = fixed_time.elapsed_secs();
+
+    for (bot_entity, bot_position, rotation, mut angular_velocity, ship, perception, skill, mut action_state, squad_member) in bots.iter_mut() {
+        action_state.release(&PlayerInput::ShootPrimary);
+        if !bot_settings.shooting {
+            continue;
+        }
+
+        let Ok(mut bot_target) = targets.get_mut(bot_entity) else { continue };
+        let Some(memory) = perception.memory.as_ref().filter(|memory| memory.in_sight) else { continue };
+
+        let Ok(dir_to_target) = Dir3::new(memory.last_known_position - bot_position.0) else { continue };
+        let aim_error = skill.0.aim_error();
+        let aim_dir = Quat::from_euler(
+            EulerRot::YXZ,
+            (rand::random::<f32>() * 2.0 - 1.0) * aim_error,
+            (rand::random::<f32>() * 2.0 - 1.0) * aim_error,
+            0.0,
+        ) * dir_to_target.as_vec3();
+        let aim_rotation = Quat::from_rotation_arc(Vec3::NEG_Z, aim_dir.normalize());
+        // turn the shortest way
+        let (axis, angle) = (aim_rotation * rotation.0.inverse()).to_axis_angle();
+        let angle = if angle > core::f32::consts::PI { angle - core::f32::consts::TAU } else { angle };
+        let max_rotation_speed = ships_data.ships.get(&ship.0).map_or(f32::INFINITY, |behavior| behavior.max_rotation_speed);
+        angular_velocity.0 = (axis * angle * BOT_TURN_RATE).clamp_length_max(max_rotation_speed);
+
+        bot_target.reaction_timer += delta;
+        // squad members take turns shooting
+        let can_fire = squad_member.is_none_or(|member| member.can_fire(elapsed));
+        if bot_target.reaction_timer >= skill.0.reaction_time && can_fire {
+            action_state.press(&PlayerInput::ShootPrimary);
+        }
+    }
+}
+
+/// Remember where sounds came from so the bots can react to them in the next [`hearing_system`].
+fn hear_weapon_fired(
+    trigger: Trigger<WeaponFiredEvent>,
+    mut heard_sounds: ResMut<HeardSounds>,
+) {
+    let event = trigger.event();
+    heard_sounds.0.push(HeardSound {
+        source: event.shooter_entity,
+        position: event.fire_origin,
+    });
+}
+
+/// Bots that don't currently see a target will investigate nearby weapons fire and projectile hits.
+/// The bot doesn't know exactly where the culprit is, so it goes to search where the noise happened.
+fn hearing_system(
+    mut commands: Commands,
+    mut heard_sounds: ResMut<HeardSounds>,
+    mut hit_events: EventReader<ProjectileHitEvent>,
+    mut bots: Query<(Entity, &Position, &Ship, &mut BotPerception, Option<&Team>), With<BotBrain>>,
+    players: Query<(Entity, &UniqueIdentity, &Position), With<PlayerShip>>,
+    positions: Query<&Position>,
+    teams: Query<&Team>,
+    ships_data: Res<ShipsData>,
+) {
+    let mut sounds = std::mem::take(&mut heard_sounds.0);
+    for event in hit_events.read() {
+        let Some(entity_hit) = event.entity_hit else { continue };
+        let Ok(hit_position) = positions.get(entity_hit) else { continue };
+        let Some((shooter_entity, _, shooter_position)) = players.iter()
+            .find(|(_, identity, _)| **identity == event.shooter_id) else { continue };
+        // a bot that gets hit knows where the shot came from, otherwise it investigates the impact
+        let position = if bots.contains(entity_hit) {
+            shooter_position.0
+        } else {
+            hit_position.0
+        };
+        sounds.push(HeardSound {
+            source: shooter_entity,
+            position,
+        });
+    }
+
+    for sound in sounds {
+        // bots only care about noises made by players
+        if !players.contains(sound.source) {
+            continue;
+        }
+        let source_team = teams.get(sound.source).ok();
+        for (bot_entity, bot_position, ship_index, mut perception, bot_team) in bots.iter_mut() {
+            // a possessed player ship doesn't chase itself, nor its teammates
+            if bot_entity == sound.source || (bot_team.is_some() && bot_team == source_team) {
+                continue;
+            }
+            let Some(ship_behavior) = ships_data.ships.get(&ship_index.0) else { continue };
+            let hearing_range = ship_behavior.bot_behavior.perception.hearing_range;
+            if bot_position.0.distance(sound.position) > hearing_range {
+                continue;
+            }
+            // what we see always takes priority over what we hear
+            if perception.memory.as_ref().is_some_and(|memory| memory.in_sight) {
+                continue;
+            }
+            if perception.memory.as_ref().is_none_or(|memory| memory.entity != sound.source) {
+                commands.entity(bot_entity).insert(BotTarget::new(sound.source));
+            }
+            perception.memory = Some(BotMemory {
+                entity: sound.source,
+                last_known_position: sound.position,
+                in_sight: false,
+                time_since_seen: 0.0,
+            });
         }
     }
 }
 
-/// Track the nearest visible player and set the bot's target to the player
+/// Track the nearest visible player and set the bot's target to the player.
+///
+/// A player is visible if it is within the bot's sight range and field of view, and nothing blocks the line of sight.
+/// When the target goes out of sight, the bot remembers its last known position and searches there until it either
+/// finds the target again, reaches that position, or forgets about it.
 fn target_tracking_system(
     spatial_query: SpatialQuery,
+    fixed_time: Res<Time<Fixed>>,
     mut commands: Commands,
-    targets: Query<&BotTarget>,
-    bots: Query<(Entity, &Position, &Children), With<BotShip>>,
-    players: Query<(Entity, &Position), With<PlayerShip>>,
+    mut bots: Query<(Entity, &Position, &Rotation, &Ship, &BotBrain, &mut BotPerception, Option<&Children>, Option<&Team>)>,
+    players: Query<(Entity, &Position, Option<&Team>), With<PlayerShip>>,
+    ships_data: Res<ShipsData>,
 ) {
-    for (bot_entity, bot_position, children) in bots.iter() {
+    let delta = fixed_time.delta_secs();
+
+    for (bot_entity, bot_position, bot_rotation, ship_index, bot, mut perception, children, bot_team) in bots.iter_mut() {
+        let Some(ship_behavior) = ships_data.ships.get(&ship_index.0) else { continue };
+        let behavior = &ship_behavior.bot_behavior.perception;
+
+        // the bot looks where it wants to go, or straight ahead if it's idle
+        let forward = if bot.wish_dir != Vec3::ZERO {
+            bot.wish_dir.normalize()
+        } else {
+            bot_rotation.0 * Vec3::NEG_Z
+        };
+        let half_fov_cos = (behavior.field_of_view_degrees.to_radians() * 0.5).cos();
+        let current_target = perception.memory.as_ref().map(|memory| memory.entity);
+
         let mut nearest_player = None;
         let mut nearest_distance = f32::MAX;
 
         // We also exclude children of the bot, because we don't want to target the bot itself at all.
         // And LagCompensationHistory is a child of the bot too.
-        let mut excluded_entities = children.collection().clone();
+        let mut excluded_entities = children.map(|children| children.collection().clone()).unwrap_or_default();
         excluded_entities.push(bot_entity);
 
-        for (player_entity, player_position) in players.iter() {
+        for (player_entity, player_position, player_team) in players.iter() {
+            // a possessed player ship doesn't target itself
+            if player_entity == bot_entity {
+                continue;
+            }
+            // nor its teammates
+            if bot_team.is_some() && bot_team == player_team {
+                continue;
+            }
             let distance = bot_position.0.distance(player_position.0);
+            if distance > behavior.sight_range {
+                continue;
+            }
             let direction = (player_position.0 - bot_position.0).normalize();
 
+            // a target we are already tracking can be followed outside of the field of view,
+            // otherwise the bot would lose it as soon as it starts orbiting
+            if current_target != Some(player_entity) && forward.dot(direction) < half_fov_cos {
+                continue;
+            }
+
             // check if the player is visible from the bot's perspective
             if let Some(hit) = spatial_query.cast_ray(
                 bot_position.0,
@@ -263,17 +643,37 @@ fn target_tracking_system(
                     .with_excluded_entities(excluded_entities.clone()),
             ) {
                 if hit.entity == player_entity && distance < nearest_distance {
-                    nearest_player = Some(player_entity);
+                    nearest_player = Some((player_entity, player_position.0));
                     nearest_distance = distance;
                 }
             }
         }
 
-        if let Some(player_entity) = nearest_player {
-            if !targets.contains(bot_entity) {
+        if let Some((player_entity, player_position)) = nearest_player {
+            if current_target != Some(player_entity) {
                 commands.entity(bot_entity).insert(BotTarget::new(player_entity));
             }
+            perception.memory = Some(BotMemory {
+                entity: player_entity,
+                last_known_position: player_position,
+                in_sight: true,
+                time_since_seen: 0.0,
+            });
+            continue;
+        }
+
+        let forget = if let Some(memory) = perception.memory.as_mut() {
+            memory.in_sight = false;
+            memory.time_since_seen += delta;
+            memory.time_since_seen > behavior.memory_duration
+                || bot_position.0.distance(memory.last_known_position) < behavior.search_radius
+                || !players.contains(memory.entity)
         } else {
+            false
+        };
+
+        if forget {
+            perception.memory = None;
             commands.entity(bot_entity).remove::<BotTarget>();
         }
     }
@@ -0,0 +1,149 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use lightyear::prelude::server::*;
+use shared::prelude::{ChatChannel, ChatMessage, ChatScope, ShipDestroyedEvent, Team, UniqueIdentity, WeaponsData, MAX_CHAT_MESSAGE_LEN};
+
+use crate::player::{DuplicateClient, PlayerMessageSender};
+
+/// Checks the chat messages of the clients and forwards them,
+/// and tells everyone when players join, leave or get shot down
+pub(crate) struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(player_joined_system);
+        app.add_observer(player_left_system);
+        app.add_observer(ship_destroyed_system);
+        app.add_systems(Update, receive_chat_system);
+    }
+}
+
+/// How many messages a client can send in a row
+const CHAT_BURST: f32 = 5.0;
+/// How many messages per second a client can send once its burst is used up
+const CHAT_MESSAGES_PER_SEC: f32 = 1.0;
+
+/// Limits how fast a client can send chat messages. Lives on the client entity.
+#[derive(Component, Debug)]
+struct ChatRateLimit {
+    /// The number of messages the client can send right now, refills over time up to [`CHAT_BURST`]
+    allowance: f32,
+}
+
+impl Default for ChatRateLimit {
+    fn default() -> Self {
+        Self { allowance: CHAT_BURST }
+    }
+}
+
+/// Sends chat messages to the clients that should read them
+#[derive(SystemParam)]
+struct ChatSender<'w, 's> {
+    messages: PlayerMessageSender<'w, 's>,
+    clients: Query<'w, 's, (&'static RemoteId, Option<&'static Team>), (With<ClientOf>, With<Connected>, Without<DuplicateClient>)>,
+}
+
+impl ChatSender<'_, '_> {
+    /// Send the message to every client that passes the filter
+    fn send(&mut self, message: ChatMessage, mut filter: impl FnMut(Option<&Team>) -> bool) {
+        let targets = self.clients.iter()
+            .filter(|(_, team)| filter(*team))
+            .map(|(remote_id, _)| remote_id.0);
+        self.messages.send_to_many::<ChatMessage, ChatChannel>(targets, message);
+    }
+
+    fn send_to_all(&mut self, message: ChatMessage) {
+        self.send(message, |_| true);
+    }
+
+    /// Tell a single client something, for example why its message was refused
+    fn notify(&mut self, client_id: PeerId, text: impl Into<String>) {
+        self.messages.send::<ChatMessage, ChatChannel>(client_id, ChatMessage::system(text));
+    }
+}
+
+/// The identity is only inserted once the server accepted the client
+fn player_joined_system(
+    trigger: Trigger<OnAdd, UniqueIdentity>,
+    clients: Query<&UniqueIdentity, With<ClientOf>>,
+    mut commands: Commands,
+    mut chat: ChatSender,
+) {
+    let Ok(identity) = clients.get(trigger.target()) else { return };
+    commands.entity(trigger.target()).insert(ChatRateLimit::default());
+    chat.send_to_all(ChatMessage::system(format!("{identity} joined the game")));
+}
+
+fn player_left_system(
+    trigger: Trigger<OnAdd, Disconnected>,
+    clients: Query<&UniqueIdentity, (With<ClientOf>, Without<DuplicateClient>)>,
+    mut chat: ChatSender,
+) {
+    let Ok(identity) = clients.get(trigger.target()) else { return };
+    chat.send_to_all(ChatMessage::system(format!("{identity} left the game")));
+}
+
+fn ship_destroyed_system(
+    trigger: Trigger<ShipDestroyedEvent>,
+    weapons_data: Option<Res<WeaponsData>>,
+    mut chat: ChatSender,
+) {
+    let event = trigger.event();
+    let text = match event.killer {
+        Some(killer) if killer == event.victim => format!("{} shot themselves down", event.victim),
+        Some(killer) => {
+            let weapon = event.weapon_index
+                .and_then(|index| weapons_data.as_ref()?.weapons.get(&index).map(|weapon| weapon.name.clone()));
+            match weapon {
+                Some(weapon) => format!("{killer} shot down {} with {weapon}", event.victim),
+                None => format!("{killer} shot down {}", event.victim),
+            }
+        }
+        None => format!("{} crashed", event.victim),
+    };
+    chat.send_to_all(ChatMessage::system(text));
+}
+
+/// Check the messages the clients sent, and forward the valid ones
+fn receive_chat_system(
+    time: Res<Time>,
+    mut clients: Query<(&RemoteId, &UniqueIdentity, Option<&Team>, &mut ChatRateLimit, &mut MessageReceiver<ChatMessage>), (With<ClientOf>, With<Connected>)>,
+    mut chat: ChatSender,
+) {
+    for (remote_id, identity, team, mut rate_limit, mut receiver) in clients.iter_mut() {
+        rate_limit.allowance = (rate_limit.allowance + time.delta_secs() * CHAT_MESSAGES_PER_SEC).min(CHAT_BURST);
+        for message in receiver.receive() {
+            let text: String = message.text.chars().filter(|c| !c.is_control()).collect();
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if text.chars().count() > MAX_CHAT_MESSAGE_LEN {
+                chat.notify(remote_id.0, format!("Messages are limited to {MAX_CHAT_MESSAGE_LEN} characters"));
+                continue;
+            }
+            if rate_limit.allowance < 1.0 {
+                chat.notify(remote_id.0, "You are sending messages too fast");
+                continue;
+            }
+            rate_limit.allowance -= 1.0;
+
+            let forwarded = ChatMessage {
+                author: Some(identity.to_string()),
+                scope: message.scope,
+                text: text.to_string(),
+            };
+            match message.scope {
+                ChatScope::All => chat.send_to_all(forwarded),
+                ChatScope::Team => {
+                    let Some(team) = team.copied() else {
+                        chat.notify(remote_id.0, "Team chat is only available in team games");
+                        continue;
+                    };
+                    chat.send(forwarded, |other| other == Some(&team));
+                }
+            }
+        }
+    }
+}
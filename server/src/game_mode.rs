@@ -0,0 +1,442 @@
+use avian3d::prelude::Position;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_config_stack::prelude::ConfigAssetLoaderPlugin;
+use lightyear::prelude::*;
+use lightyear::prelude::server::*;
+use shared::bot::BotShip;
+use shared::player::PlayerShip;
+use shared::prelude::{Damageable, EnemySpawnPoint, GameMode, GameModeRules, GameModesData, HeldKeys, KeyPickup, KeySpawnPoint, MatchPhase, MatchResult, MatchState, Scoreboard, Ship, ShipColliders, ShipDestroyedEvent, ShipsData, Team, UniqueIdentity, WeaponsData};
+
+use crate::bot::{spawn_bot, BotSettings, BotSpawn};
+
+/// Runs the matches: warmup, the match itself and the results, then a new match.
+/// Assigns the teams, decides who can damage whom, and places the enemies and keys of the map in co-op.
+pub(crate) struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ConfigAssetLoaderPlugin::<GameModesData>::new("data/game_modes.ron"));
+        app.add_observer(spawn_match_system);
+        app.add_observer(assign_player_team_system);
+        app.add_observer(assign_bot_team_system);
+        app.add_observer(ship_destroyed_system);
+        app.add_observer(start_match_system);
+        app.add_systems(Update, (
+            match_timer_system,
+            score_limit_system,
+            pick_up_keys_system.run_if(|game_mode: Res<GameMode>| *game_mode == GameMode::Coop),
+        ).chain()
+            .run_if(any_with_component::<MatchState>)
+            .run_if(resource_exists::<GameModesData>));
+    }
+}
+
+/// How close (in meters) a ship must fly to a key to pick it up
+const KEY_PICKUP_RADIUS: f32 = 2.0;
+
+/// Triggered on the server when the match enters a new phase
+#[derive(Event, Debug, Clone, Copy)]
+pub(crate) struct MatchPhaseChanged(pub(crate) MatchPhase);
+
+/// Counts down the current phase. Lives on the entity with the [`MatchState`].
+#[derive(Component, Debug, Default)]
+struct MatchTimer(
+    /// `None` while the warmup waits for players, or if the match has no time limit
+    Option<Timer>,
+);
+
+/// How many co-op enemies the current match started with. Lives on the entity with the [`MatchState`].
+/// The match is only won once they have been spawned and destroyed.
+#[derive(Component, Debug, Default)]
+struct SpawnedEnemies(u32);
+
+/// Decides who can damage whom, depending on the teams and the phase of the match
+#[derive(SystemParam)]
+pub(crate) struct DamageRules<'w, 's> {
+    game_mode: Res<'w, GameMode>,
+    game_modes_data: Option<Res<'w, GameModesData>>,
+    match_state: Query<'w, 's, &'static MatchState>,
+    teams: Query<'w, 's, (&'static UniqueIdentity, &'static Team)>,
+}
+
+impl DamageRules<'_, '_> {
+    pub(crate) fn allows(&self, attacker: UniqueIdentity, victim: UniqueIdentity) -> bool {
+        // the match is over, let everyone look at the results in peace
+        if self.match_state.single().is_ok_and(|state| state.phase == MatchPhase::PostMatch) {
+            return false;
+        }
+        if attacker == victim {
+            return true;
+        }
+        let team_of = |identity: UniqueIdentity| self.teams.iter()
+            .find(|(other, _)| **other == identity)
+            .map(|(_, team)| *team);
+        match (team_of(attacker), team_of(victim)) {
+            (Some(attacker_team), Some(victim_team)) if attacker_team == victim_team => {
+                self.game_modes_data.as_ref().is_some_and(|data| data.rules(*self.game_mode).friendly_fire)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// The match lives as long as the server, it is despawned with the other replicated entities when it stops
+fn spawn_match_system(
+    _: Trigger<OnAdd, Started>,
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+) {
+    info!("Starting a {:?} warmup", *game_mode);
+    commands.spawn((
+        Name::from("Match"),
+        MatchState {
+            game_mode: *game_mode,
+            ..default()
+        },
+        MatchTimer::default(),
+        SpawnedEnemies::default(),
+        Replicate::to_clients(NetworkTarget::All),
+    ));
+}
+
+/// Put new players in the team with the fewest members, everyone plays for blue in co-op
+fn assign_player_team_system(
+    trigger: Trigger<OnAdd, UniqueIdentity>,
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    clients: Query<(), With<ClientOf>>,
+    teams: Query<&Team, Or<(With<ClientOf>, With<BotShip>)>>,
+) {
+    if !clients.contains(trigger.target()) {
+        return;
+    }
+    let team = match *game_mode {
+        GameMode::Deathmatch => return,
+        GameMode::TeamDeathmatch => smallest_team(&teams),
+        GameMode::Coop => Team::Blue,
+    };
+    commands.entity(trigger.target()).insert(team);
+}
+
+/// Bots balance the teams too, in co-op they are the enemies
+fn assign_bot_team_system(
+    trigger: Trigger<OnAdd, BotShip>,
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    teams: Query<&Team, Or<(With<ClientOf>, With<BotShip>)>>,
+) {
+    let team = match *game_mode {
+        GameMode::Deathmatch => return,
+        GameMode::TeamDeathmatch => smallest_team(&teams),
+        GameMode::Coop => Team::Red,
+    };
+    commands.entity(trigger.target()).insert(team);
+}
+
+fn smallest_team(teams: &Query<&Team, Or<(With<ClientOf>, With<BotShip>)>>) -> Team {
+    let red = teams.iter().filter(|team| **team == Team::Red).count();
+    let blue = teams.iter().filter(|team| **team == Team::Blue).count();
+    if blue < red { Team::Blue } else { Team::Red }
+}
+
+fn set_phase(
+    commands: &mut Commands,
+    state: &mut MatchState,
+    timer: &mut MatchTimer,
+    rules: &GameModeRules,
+    phase: MatchPhase,
+) {
+    info!("The match is now in {:?}", phase);
+    state.phase = phase;
+    timer.0 = match phase {
+        // started once a player is there
+        MatchPhase::Warmup => None,
+        MatchPhase::InProgress => rules.time_limit_secs.map(|secs| Timer::from_secs_f32(secs, TimerMode::Once)),
+        MatchPhase::PostMatch => Some(Timer::from_secs_f32(rules.post_match_secs, TimerMode::Once)),
+    };
+    state.time_left = timer.0.as_ref().map(|timer| timer.remaining_secs().ceil() as u32);
+    if phase != MatchPhase::PostMatch {
+        state.result = None;
+    }
+    commands.trigger(MatchPhaseChanged(phase));
+}
+
+fn match_timer_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    game_modes_data: Res<GameModesData>,
+    scoreboard: Query<&Scoreboard>,
+    players: Query<(), (With<ClientOf>, With<Connected>)>,
+    enemy_spawn_points: Query<&EnemySpawnPoint>,
+    ships_data: Option<Res<ShipsData>>,
+    weapons_data: Option<Res<WeaponsData>>,
+    ship_colliders: Res<ShipColliders>,
+    match_state: Single<(&mut MatchState, &mut MatchTimer)>,
+) {
+    let (mut state, mut timer) = match_state.into_inner();
+    let rules = game_modes_data.rules(*game_mode);
+
+    if state.phase == MatchPhase::Warmup {
+        if players.is_empty() {
+            timer.0 = None;
+            if state.time_left.is_some() {
+                state.time_left = None;
+            }
+            return;
+        }
+        if timer.0.is_none() {
+            timer.0 = Some(Timer::from_secs_f32(rules.warmup_secs, TimerMode::Once));
+        }
+    }
+    let Some(phase_timer) = timer.0.as_mut() else { return };
+    phase_timer.tick(time.delta());
+    // only replicate the timer once per second
+    let time_left = Some(phase_timer.remaining_secs().ceil() as u32);
+    if state.time_left != time_left {
+        state.time_left = time_left;
+    }
+    if !phase_timer.finished() {
+        return;
+    }
+
+    match state.phase {
+        MatchPhase::Warmup => {
+            // there is nothing to win in co-op without enemies, warm up again until they can be spawned
+            if state.game_mode == GameMode::Coop {
+                let can_spawn_enemies = weapons_data.is_some() && enemy_spawn_points.iter().any(|enemy_spawn_point| {
+                    game_modes_data.enemy_ships.get(&enemy_spawn_point.0).is_some_and(|ship_id| {
+                        ships_data.as_ref().is_some_and(|ships_data| ships_data.ships.contains_key(ship_id))
+                            && ship_colliders.get(*ship_id).is_some()
+                    })
+                });
+                if !can_spawn_enemies {
+                    warn!("The co-op match cannot start: no enemy can be spawned on this map yet");
+                    timer.0 = None;
+                    return;
+                }
+            }
+            state.score_limit = rules.score_limit;
+            set_phase(&mut commands, &mut state, &mut timer, &rules, MatchPhase::InProgress);
+        }
+        MatchPhase::InProgress => {
+            state.result = Some(time_limit_result(&state, scoreboard.single().ok()));
+            set_phase(&mut commands, &mut state, &mut timer, &rules, MatchPhase::PostMatch);
+        }
+        MatchPhase::PostMatch => {
+            set_phase(&mut commands, &mut state, &mut timer, &rules, MatchPhase::Warmup);
+        }
+    }
+}
+
+/// Who won when the time ran out
+fn time_limit_result(state: &MatchState, scoreboard: Option<&Scoreboard>) -> MatchResult {
+    match state.game_mode {
+        GameMode::Deathmatch => {
+            let ranked = scoreboard.map(|scoreboard| scoreboard.ranked()).unwrap_or_default();
+            match ranked.as_slice() {
+                [(_, first), (_, second), ..] if first.kills == second.kills => MatchResult::Draw,
+                [(leader, _), ..] => MatchResult::Winner(*leader),
+                [] => MatchResult::Draw,
+            }
+        }
+        GameMode::TeamDeathmatch => match state.red_score.cmp(&state.blue_score) {
+            core::cmp::Ordering::Greater => MatchResult::WinningTeam(Team::Red),
+            core::cmp::Ordering::Less => MatchResult::WinningTeam(Team::Blue),
+            core::cmp::Ordering::Equal => MatchResult::Draw,
+        },
+        GameMode::Coop => MatchResult::Defeat,
+    }
+}
+
+/// End the match as soon as someone reaches the score limit, or the last enemy is destroyed in co-op
+fn score_limit_system(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    game_modes_data: Res<GameModesData>,
+    scoreboard: Query<&Scoreboard>,
+    match_state: Single<(&mut MatchState, &mut MatchTimer, &SpawnedEnemies)>,
+) {
+    let (mut state, mut timer, spawned_enemies) = match_state.into_inner();
+    if state.phase != MatchPhase::InProgress {
+        return;
+    }
+    let result = match (state.game_mode, state.score_limit) {
+        (GameMode::Coop, _) => (spawned_enemies.0 > 0 && state.enemies_left == 0).then_some(MatchResult::Victory),
+        (GameMode::Deathmatch, Some(score_limit)) => scoreboard.single().ok()
+            .and_then(|scoreboard| scoreboard.ranked().first().map(|(identity, score)| (*identity, score.kills)))
+            .filter(|(_, kills)| *kills >= score_limit)
+            .map(|(identity, _)| MatchResult::Winner(identity)),
+        (GameMode::TeamDeathmatch, Some(score_limit)) => {
+            if state.red_score >= score_limit {
+                Some(MatchResult::WinningTeam(Team::Red))
+            } else if state.blue_score >= score_limit {
+                Some(MatchResult::WinningTeam(Team::Blue))
+            } else {
+                None
+            }
+        }
+        (_, None) => None,
+    };
+    if let Some(result) = result {
+        state.result = Some(result);
+        let rules = game_modes_data.rules(*game_mode);
+        set_phase(&mut commands, &mut state, &mut timer, &rules, MatchPhase::PostMatch);
+    }
+}
+
+/// Everyone starts the match on equal terms, in co-op the map's enemies and keys are placed
+fn start_match_system(
+    trigger: Trigger<MatchPhaseChanged>,
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    game_modes_data: Option<Res<GameModesData>>,
+    bot_settings: Res<BotSettings>,
+    ships_data: Option<Res<ShipsData>>,
+    ship_colliders: Res<ShipColliders>,
+    weapons_data: Option<Res<WeaponsData>>,
+    mut match_state: Query<(&mut MatchState, &mut SpawnedEnemies)>,
+    mut scoreboard: Query<&mut Scoreboard>,
+    mut ships: Query<(&Ship, &mut Damageable, Option<&mut HeldKeys>)>,
+    coop_leftovers: Query<Entity, Or<(With<BotShip>, With<KeyPickup>)>>,
+    enemy_spawn_points: Query<(&EnemySpawnPoint, &GlobalTransform)>,
+    key_spawn_points: Query<(&KeySpawnPoint, &GlobalTransform)>,
+) {
+    let MatchPhaseChanged(phase) = *trigger.event();
+    if *game_mode == GameMode::Coop && phase != MatchPhase::PostMatch {
+        for entity in coop_leftovers.iter() {
+            commands.entity(entity).try_despawn();
+        }
+    }
+    if phase != MatchPhase::InProgress {
+        return;
+    }
+    let Ok((mut state, mut spawned_enemies)) = match_state.single_mut() else { return };
+    state.red_score = 0;
+    state.blue_score = 0;
+    state.enemies_left = 0;
+    spawned_enemies.0 = 0;
+    if let Ok(mut scoreboard) = scoreboard.single_mut() {
+        scoreboard.reset();
+    }
+    if let Some(ships_data) = ships_data.as_ref() {
+        for (ship, mut damageable, held_keys) in ships.iter_mut() {
+            if let Some(ship_data) = ships_data.ships.get(&ship.0) {
+                damageable.health = ship_data.starting_health;
+            }
+            if let Some(mut held_keys) = held_keys {
+                *held_keys = HeldKeys::default();
+            }
+        }
+    }
+    if *game_mode != GameMode::Coop {
+        return;
+    }
+
+    for (key_spawn_point, transform) in key_spawn_points.iter() {
+        commands.spawn((
+            Name::from(format!("{:?} Key", key_spawn_point.0)),
+            KeyPickup {
+                color: key_spawn_point.0,
+                position: transform.translation(),
+            },
+            Replicate::to_clients(NetworkTarget::All),
+        ));
+    }
+
+    let (Some(game_modes_data), Some(ships_data), Some(weapons_data)) = (game_modes_data, ships_data, weapons_data) else {
+        error!("The co-op enemies cannot be spawned before the game data is loaded");
+        return;
+    };
+    let mut bot_id = 0;
+    for (enemy_spawn_point, transform) in enemy_spawn_points.iter() {
+        let Some(ship_id) = game_modes_data.enemy_ships.get(&enemy_spawn_point.0).copied() else {
+            warn!("No ship is defined for {:?} enemies", enemy_spawn_point.0);
+            continue;
+        };
+        let (Some(ship_data), Some(collider)) = (ships_data.ships.get(&ship_id), ship_colliders.get(ship_id)) else {
+            warn!("Ship {ship_id} is not ready for {:?} enemies", enemy_spawn_point.0);
+            continue;
+        };
+        let spawn = BotSpawn {
+            bot_id,
+            ship_id,
+            difficulty: bot_settings.difficulty,
+            position: transform.translation(),
+            rotation: transform.rotation(),
+        };
+        spawn_bot(&mut commands, spawn, ship_data, collider, &weapons_data);
+        bot_id += 1;
+    }
+    state.enemies_left = bot_id;
+    spawned_enemies.0 = bot_id;
+    info!("Spawned {} co-op enemies", state.enemies_left);
+}
+
+/// Count the team kills and the enemies left, and drop the keys of destroyed ships back where they were found
+fn ship_destroyed_system(
+    trigger: Trigger<ShipDestroyedEvent>,
+    mut commands: Commands,
+    teams: Query<(&UniqueIdentity, &Team)>,
+    held_keys: Query<&HeldKeys>,
+    key_spawn_points: Query<(&KeySpawnPoint, &GlobalTransform)>,
+    mut match_state: Query<&mut MatchState>,
+) {
+    let event = trigger.event();
+    let Ok(mut state) = match_state.single_mut() else { return };
+    if state.phase != MatchPhase::InProgress {
+        return;
+    }
+    let team_of = |identity: UniqueIdentity| teams.iter()
+        .find(|(other, _)| **other == identity)
+        .map(|(_, team)| *team);
+    let victim_team = team_of(event.victim);
+
+    match state.game_mode {
+        GameMode::Deathmatch => {}
+        GameMode::TeamDeathmatch => {
+            let killer_team = event.killer.filter(|killer| *killer != event.victim).and_then(team_of);
+            // shooting down a teammate doesn't score
+            match killer_team {
+                Some(Team::Red) if victim_team != Some(Team::Red) => state.red_score += 1,
+                Some(Team::Blue) if victim_team != Some(Team::Blue) => state.blue_score += 1,
+                _ => {}
+            }
+        }
+        GameMode::Coop => {
+            if matches!(event.victim, UniqueIdentity::Bot(_)) && victim_team == Some(Team::Red) {
+                state.enemies_left = state.enemies_left.saturating_sub(1);
+                state.blue_score += 1;
+            }
+            let Ok(keys) = held_keys.get(event.ship) else { return };
+            for (key_spawn_point, transform) in key_spawn_points.iter() {
+                if keys.has(key_spawn_point.0) {
+                    commands.spawn((
+                        Name::from(format!("{:?} Key", key_spawn_point.0)),
+                        KeyPickup {
+                            color: key_spawn_point.0,
+                            position: transform.translation(),
+                        },
+                        Replicate::to_clients(NetworkTarget::All),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn pick_up_keys_system(
+    mut commands: Commands,
+    keys: Query<(Entity, &KeyPickup)>,
+    mut ships: Query<(&Position, &mut HeldKeys), With<PlayerShip>>,
+) {
+    for (key_entity, key) in keys.iter() {
+        let picked_up_by = ships.iter_mut()
+            .find(|(position, _)| position.0.distance(key.position) <= KEY_PICKUP_RADIUS);
+        if let Some((_, mut held_keys)) = picked_up_by {
+            held_keys.give(key.color);
+            commands.entity(key_entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use shared::prelude::{DamageTaken, DamageTakenChannel, DamageType, Damageable, PreImpactVelocity, ShipDestroyedEvent, ShipImpactChannel, ShipImpactEvent, UniqueIdentity};
+use shared::ships::{Ship, ShipsData};
+
+use crate::game_mode::DamageRules;
+use crate::player::PlayerMessageSender;
+
+/// Damages ships that slam into walls or other ships
+pub(crate) struct ImpactsPlugin;
+
+impl Plugin for ImpactsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedPostUpdate, (
+            record_pre_impact_velocity_system.before(PhysicsSet::StepSimulation),
+            collision_damage_system
+                .after(PhysicsSet::StepSimulation)
+                .run_if(resource_exists::<ShipsData>),
+        ));
+    }
+}
+
+/// The solver changes the velocities when resolving a collision,
+/// so we remember how fast the ships were going before the physics step
+fn record_pre_impact_velocity_system(
+    mut ships: Query<(&LinearVelocity, &mut PreImpactVelocity), With<Replicating>>,
+) {
+    for (linear_velocity, mut pre_impact_velocity) in ships.iter_mut() {
+        pre_impact_velocity.0 = linear_velocity.0;
+    }
+}
+
+/// Apply damage to the ships that started colliding during this physics step.
+/// The damage depends on the speed along the contact normal, walls count as not moving.
+fn collision_damage_system(
+    mut commands: Commands,
+    mut messages: PlayerMessageSender,
+    damage_rules: DamageRules,
+    ships_data: Res<ShipsData>,
+    mut collision_started: EventReader<CollisionStarted>,
+    collisions: Collisions,
+    velocities: Query<&PreImpactVelocity>,
+    mut ships: Query<(&Ship, &Position, &mut Damageable, &UniqueIdentity), With<Replicating>>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_started.read() {
+        let Some(contact_pair) = collisions.get(*entity1, *entity2) else { continue };
+        let Some(manifold) = contact_pair.manifolds.first() else { continue };
+
+        let velocity1 = velocities.get(*entity1).map_or(Vec3::ZERO, |velocity| velocity.0);
+        let velocity2 = velocities.get(*entity2).map_or(Vec3::ZERO, |velocity| velocity.0);
+        // both ships take the full impact, ramming hurts
+        let impact_speed = (velocity1 - velocity2).dot(manifold.normal).abs();
+
+        for entity in [*entity1, *entity2] {
+            let Ok((ship, position, mut damageable, identity)) = ships.get_mut(entity) else { continue };
+            // the ship was already destroyed this frame, by another impact or a projectile
+            if damageable.health == 0 {
+                continue;
+            }
+            // nobody is to blame for an impact, the ship hurts itself
+            if !damage_rules.allows(*identity, *identity) {
+                continue;
+            }
+            let Some(ship_data) = ships_data.ships.get(&ship.0) else { continue };
+
+            let damage = ship_data.collision_damage.damage(impact_speed);
+            if damage == 0 {
+                continue;
+            }
+            damageable.health = damageable.health.saturating_sub(damage);
+            debug!(?entity, ?impact_speed, ?damage, "Ship impact");
+
+            let impact_event = ShipImpactEvent {
+                entity,
+                position: position.0,
+                impact_speed,
+                damage,
+            };
+            commands.trigger(impact_event.clone());
+            messages.broadcast::<ShipImpactEvent, ShipImpactChannel>(&impact_event);
+
+            if let UniqueIdentity::Player(victim) = *identity {
+                // the normal points from the first collider to the second,
+                // a point in the direction of the impact is enough to show where it came from
+                let towards_impact = if entity == contact_pair.collider1 { manifold.normal } else { -manifold.normal };
+                messages.send::<_, DamageTakenChannel>(victim, DamageTaken {
+                    damage,
+                    damage_type: DamageType::Collision,
+                    attacker_position: position.0 + towards_impact,
+                });
+            }
+
+            if damageable.health == 0 {
+                commands.trigger(ShipDestroyedEvent {
+                    ship: entity,
+                    victim: *identity,
+                    killer: None,
+                    weapon_index: None,
+                });
+                commands.entity(entity).try_despawn();
+            }
+        }
+    }
+}
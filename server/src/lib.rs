@@ -1,10 +1,21 @@
 mod player;
 mod bot;
+mod chat;
+mod game_mode;
+mod impacts;
+mod map;
+mod possession;
+mod scoreboard;
+mod squad;
 mod weapons;
 
 use bevy::prelude::*;
+use lightyear::prelude::*;
 use lightyear::prelude::server::*;
 
+pub use bot::BotSettings;
+pub use map::MapRotation;
+
 pub struct ServerPlugin;
 
 
@@ -13,12 +24,20 @@ impl Plugin for ServerPlugin {
 
         // PLUGINS
         app.add_plugins(bot::BotPlugin);
+        app.add_plugins(chat::ChatPlugin);
+        app.add_plugins(squad::SquadPlugin);
+        app.add_plugins(possession::PossessionPlugin);
+        app.add_plugins(scoreboard::ScoreboardPlugin);
+        app.add_plugins(game_mode::GameModePlugin);
+        app.add_plugins(map::MapRotationPlugin);
         app.add_plugins(lightyear_avian3d::prelude::LagCompensationPlugin);
         app.add_plugins(player::PlayerPlugin);
+        app.add_plugins(impacts::ImpactsPlugin);
         app.add_plugins(weapons::WeaponsPlugin);
 
         // SYSTEMS
         app.add_systems(Startup, server_start);
+        app.add_observer(server_stopped);
     }
 }
 
@@ -26,4 +45,15 @@ fn server_start(
     server: Single<Entity, With<Server>>,
     mut commands: Commands) {
     commands.entity(server.into_inner()).trigger(Start);
+}
+
+/// When a hosted game ends, remove everything it spawned so that the next game starts from scratch
+fn server_stopped(
+    _: Trigger<OnAdd, Stopped>,
+    mut commands: Commands,
+    replicated: Query<Entity, With<Replicate>>,
+) {
+    for entity in replicated.iter() {
+        commands.entity(entity).try_despawn();
+    }
 }
\ No newline at end of file
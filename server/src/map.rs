@@ -0,0 +1,117 @@
+use avian3d::prelude::Position;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use shared::prelude::{CurrentMap, KeyPickup, MatchPhase, PlayerSpawnPoint, Projectile, Ship, DEFAULT_MAP};
+
+use crate::game_mode::MatchPhaseChanged;
+
+/// Picks the map the server plays, and moves to the next map of the rotation after each match
+pub(crate) struct MapRotationPlugin;
+
+impl Plugin for MapRotationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapRotation>();
+        app.add_observer(spawn_current_map_system);
+        app.add_observer(next_map_system);
+    }
+}
+
+/// The maps the server plays, in order. The rotation starts over after the last one.
+#[derive(Resource, Debug, Clone)]
+pub struct MapRotation {
+    maps: Vec<String>,
+    current: usize,
+}
+
+impl MapRotation {
+    /// Plays the default map if `maps` is empty
+    pub fn new(maps: Vec<String>) -> Self {
+        if maps.is_empty() {
+            return Self::default();
+        }
+        Self { maps, current: 0 }
+    }
+
+    pub fn current(&self) -> &str {
+        &self.maps[self.current]
+    }
+
+    fn advance(&mut self) -> &str {
+        self.current = (self.current + 1) % self.maps.len();
+        self.current()
+    }
+}
+
+impl Default for MapRotation {
+    fn default() -> Self {
+        Self {
+            maps: vec![DEFAULT_MAP.to_string()],
+            current: 0,
+        }
+    }
+}
+
+/// Picks where the players and bots spawn, among the player spawn points of the map
+#[derive(SystemParam)]
+pub(crate) struct SpawnPoints<'w, 's> {
+    spawn_points: Query<'w, 's, &'static GlobalTransform, With<PlayerSpawnPoint>>,
+    ships: Query<'w, 's, &'static Position, With<Ship>>,
+}
+
+impl SpawnPoints<'_, '_> {
+    /// The spawn point furthest from every ship, including the ones at `spawned` that were spawned this frame.
+    /// Returns `fallback` if the map has no spawn point or isn't loaded yet.
+    pub(crate) fn pick(&self, spawned: &[Vec3], fallback: (Vec3, Quat)) -> (Vec3, Quat) {
+        let occupied = self.ships.iter()
+            .map(|position| position.0)
+            .chain(spawned.iter().copied())
+            .collect::<Vec<_>>();
+        let clearance = |point: Vec3| occupied.iter()
+            .map(|ship| ship.distance_squared(point))
+            .fold(f32::INFINITY, f32::min);
+        self.spawn_points.iter()
+            .map(|transform| (transform.translation(), transform.rotation()))
+            .max_by(|(a, _), (b, _)| clearance(*a).total_cmp(&clearance(*b)))
+            .unwrap_or(fallback)
+    }
+}
+
+/// The map lives as long as the server, it is despawned with the other replicated entities when it stops.
+/// The map scene itself stays loaded until another map replaces it.
+fn spawn_current_map_system(
+    _: Trigger<OnAdd, Started>,
+    mut commands: Commands,
+    map_rotation: Res<MapRotation>,
+) {
+    info!("Playing map {}", map_rotation.current());
+    commands.spawn((
+        Name::from("Current Map"),
+        CurrentMap(map_rotation.current().to_string()),
+        Replicate::to_clients(NetworkTarget::All),
+    ));
+}
+
+/// Once the results of a match have been shown, the next match is played on the next map.
+/// Everything that was spawned on the previous map is removed, the players and bots respawn on the new one.
+fn next_map_system(
+    trigger: Trigger<MatchPhaseChanged>,
+    mut commands: Commands,
+    mut map_rotation: ResMut<MapRotation>,
+    mut current_map: Query<&mut CurrentMap>,
+    spawned: Query<Entity, Or<(With<Ship>, With<Projectile>, With<KeyPickup>)>>,
+) {
+    if trigger.event().0 != MatchPhase::Warmup {
+        return;
+    }
+    let Ok(mut current_map) = current_map.single_mut() else { return };
+    let next_map = map_rotation.advance().to_string();
+    if current_map.0 == next_map {
+        return;
+    }
+    info!("Changing map from {} to {next_map}", current_map.0);
+    current_map.0 = next_map;
+    for entity in spawned.iter() {
+        commands.entity(entity).try_despawn();
+    }
+}
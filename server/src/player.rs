@@ -1,40 +1,129 @@
 use core::time::Duration;
 
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use lightyear::connection::host::HostClient;
 use lightyear::prelude::{server::*, *};
-use shared::{player::{PlayerRespawnTimer, PlayerShip}, prelude::{Damageable, UniqueIdentity}, ships::{get_shared_ship_components, Ship, ShipId, ShipsData}, weapons::{CurrentWeaponIndex, WeaponInventory, WeaponsData}};
+use shared::{game_mode::GameMode, player::{PlayerRespawnTimer, PlayerShip}, prelude::{Damageable, Energy, HeldKeys, Team, UniqueIdentity}, ships::{get_shared_ship_components, Dash, FlightMode, Ship, ShipColliders, ShipId, ShipSelection, ShipsData}, weapons::{CurrentWeaponIndex, WeaponInventory, WeaponsData}};
 use avian3d::prelude::*;
 
+use crate::bot::BotSettings;
+use crate::map::SpawnPoints;
+use crate::possession::{release_possession, Possessed};
+
 pub(crate) struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnPlayerShipEvent>();
+        app.init_resource::<GameMode>();
         app.add_observer(player_connect_system);
-        app.add_systems(Update, 
+        app.add_systems(Update, (
+                ship_selection_system,
+                respawn_system,
                 spawn_player_ship_system
-                    .run_if(resource_exists::<WeaponsData>)
-                    .run_if(resource_exists::<ShipsData>)
+                    .run_if(resource_exists::<WeaponsData>),
+            ).chain().run_if(resource_exists::<ShipsData>)
         );
     }
 }
 
+/// The ship the player will fly the next time they spawn.
+/// Lives on the client entity.
+#[derive(Component, Debug)]
+pub struct SelectedShip(pub ShipId);
+
+/// The ship is placed on one of the map's spawn points when it is spawned
 #[derive(Event)]
 pub struct SpawnPlayerShipEvent {
     pub client_entity: Entity,
     pub client_id: PeerId,
     pub ship_id: ShipId,
-    pub position: Vec3,
-    pub rotation: Quat,
 }
 
+/// Where the players spawn on maps without spawn points
+const FALLBACK_SPAWN_POSITION: Vec3 = Vec3::new(0.0, 2.0, 0.0);
+
+/// Sends messages to the players.
+/// The host-client doesn't receive messages, so the message is triggered as an event for it instead.
+#[derive(SystemParam)]
+pub(crate) struct PlayerMessageSender<'w, 's> {
+    commands: Commands<'w, 's>,
+    server: Single<'w, &'static Server>,
+    sender: ServerMultiMessageSender<'w, 's>,
+    clients: Query<'w, 's, (&'static RemoteId, Has<HostClient>), (With<ClientOf>, With<Connected>, Without<DuplicateClient>)>,
+}
+
+impl PlayerMessageSender<'_, '_> {
+    pub(crate) fn send<M: Message + Event, C: Channel>(&mut self, client_id: PeerId, message: M) {
+        match self.clients.iter().find(|(remote_id, _)| remote_id.0 == client_id) {
+            Some((_, true)) => self.commands.trigger(message),
+            Some((_, false)) => self.sender.send::<M, C>(
+                &message,
+                *self.server,
+                &NetworkTarget::Single(client_id),
+            ).unwrap(),
+            // the player left
+            None => {}
+        }
+    }
+    /// Send a message to several players at once
+    pub(crate) fn send_to_many<M: Message + Event, C: Channel>(&mut self, client_ids: impl IntoIterator<Item = PeerId>, message: M) {
+        let mut targets = Vec::new();
+        let mut host_reads = false;
+        for client_id in client_ids {
+            match self.clients.iter().find(|(remote_id, _)| remote_id.0 == client_id) {
+                Some((_, true)) => host_reads = true,
+                Some((_, false)) => targets.push(client_id),
+                None => {}
+            }
+        }
+        if !targets.is_empty() {
+            self.sender.send::<M, C>(
+                &message,
+                *self.server,
+                &NetworkTarget::Only(targets),
+            ).unwrap();
+        }
+        if host_reads {
+            self.commands.trigger(message);
+        }
+    }
+    /// Send a message to every client.
+    /// The caller triggers the event itself if the server should react to it too.
+    pub(crate) fn broadcast<M: Message, C: Channel>(&mut self, message: &M) {
+        self.sender.send::<M, C>(message, *self.server, &NetworkTarget::All).unwrap();
+    }
+}
+
+/// The player still flies the default ship they were given when they connected.
+/// The first ship they pick replaces it right away, later picks wait for the next respawn.
+/// Lives on the client entity.
+#[derive(Component, Debug)]
+struct FirstShip;
+
+/// Marks the link of a client that was refused because another client with the same id is already playing
+#[derive(Component, Debug)]
+pub(crate) struct DuplicateClient;
+
 fn player_connect_system(
     trigger: Trigger<OnAdd, Connected>,
     peer_id: Query<&RemoteId, With<ClientOf>>,
+    connected_clients: Query<(Entity, &RemoteId), (With<ClientOf>, With<Connected>, Without<DuplicateClient>)>,
+    possessed_ships: Query<(Entity, &Possessed)>,
+    ships_data: Option<Res<ShipsData>>,
     mut commands: Commands,
     mut spawn_player_ship_events: EventWriter<SpawnPlayerShipEvent>,
 ) {
     if let Ok(peer_id) = peer_id.get(trigger.target()) {
         let client_id = peer_id.0;
+        // the ids identify the players and their ships, two clients cannot share one
+        if connected_clients.iter().any(|(entity, remote_id)| entity != trigger.target() && remote_id.0 == client_id) {
+            warn!("Refusing client {client_id:?}: a client with the same id is already connected");
+            commands.entity(trigger.target())
+                .insert(DuplicateClient)
+                .trigger(Disconnect);
+            return;
+        }
         info!("Connection from new client: {client_id:?}");
          commands.entity(trigger.target()).insert((
             Name::from(format!("Player ({})", client_id)),
@@ -42,13 +131,93 @@ fn player_connect_system(
             PlayerRespawnTimer(Timer::new(Duration::from_secs(3), TimerMode::Once)),
         ));
 
-        spawn_player_ship_events.write(SpawnPlayerShipEvent {
-            client_entity: trigger.target(),
-            client_id,
-            ship_id: 0,
-            position: Vec3::new(0.0, 2.0, 0.0),
-            rotation: Quat::from_rotation_arc(Vec3::Y, Vec3::Z),
-        });
+        // the player came back while a bot was flying their ship, give it back to them
+        if let Some((ship, _)) = possessed_ships.iter().find(|(_, possessed)| possessed.client_id == client_id) {
+            info!("Client {client_id:?} reconnected, giving them back their ship {ship}");
+            release_possession(&mut commands, ship, trigger.target());
+            return;
+        }
+
+        // fly the default ship right away, until the client tells us which ship they want.
+        // If the ships are not loaded yet, the respawn timer spawns the player
+        if let Some(ships_data) = ships_data {
+            commands.entity(trigger.target()).insert(FirstShip);
+            spawn_player_ship_events.write(player_spawn_event(trigger.target(), client_id, ships_data.player_ship));
+        }
+    }
+}
+
+/// Handle the ship selection requests of the clients.
+/// The selection is validated against the ships allowed in the current game mode, and used the next time the player spawns.
+/// The default ship given on connect is replaced right away, picking a ship doesn't skip the respawn timer otherwise.
+fn ship_selection_system(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &RemoteId, &mut MessageReceiver<ShipSelection>, Has<FirstShip>), (With<ClientOf>, With<Connected>)>,
+    player_ships: Query<(Entity, &Ship, &UniqueIdentity), With<PlayerShip>>,
+    ships_data: Res<ShipsData>,
+    game_mode: Res<GameMode>,
+    mut spawn_player_ship_events: EventWriter<SpawnPlayerShipEvent>,
+) {
+    for (client_entity, remote_id, mut receiver, first_ship) in clients.iter_mut() {
+        let client_id = remote_id.0;
+        // only the most recent request matters
+        let Some(selection) = receiver.receive().last() else { continue };
+
+        if !ships_data.is_selectable(selection.ship_id, *game_mode) {
+            warn!("Client {client_id:?} requested ship {} which is not allowed in {:?}", selection.ship_id, *game_mode);
+            continue;
+        }
+        commands.entity(client_entity).insert(SelectedShip(selection.ship_id));
+
+        if !first_ship {
+            continue;
+        }
+        commands.entity(client_entity).remove::<FirstShip>();
+        let current_ship = player_ships.iter().find(|(_, _, identity)| **identity == UniqueIdentity::Player(client_id));
+        if let Some((ship_entity, ship, _)) = current_ship {
+            if ship.0 != selection.ship_id {
+                commands.entity(ship_entity).despawn();
+                spawn_player_ship_events.write(player_spawn_event(client_entity, client_id, selection.ship_id));
+            }
+        }
+    }
+}
+
+/// Respawn the players that don't have a ship, once their respawn timer is finished
+fn respawn_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut clients: Query<(Entity, &RemoteId, &mut PlayerRespawnTimer, Option<&SelectedShip>, Has<FirstShip>), (With<ClientOf>, With<Connected>)>,
+    player_ships: Query<&UniqueIdentity, With<PlayerShip>>,
+    ships_data: Res<ShipsData>,
+    mut spawn_player_ship_events: EventWriter<SpawnPlayerShipEvent>,
+) {
+    for (client_entity, remote_id, mut respawn_timer, selected_ship, first_ship) in clients.iter_mut() {
+        let client_id = remote_id.0;
+        let has_ship = player_ships.iter().any(|identity| *identity == UniqueIdentity::Player(client_id));
+        if has_ship {
+            respawn_timer.0.reset();
+            continue;
+        }
+        // the first ship is gone, the next one waits for the respawn timer whatever the player picks
+        if first_ship {
+            commands.entity(client_entity).remove::<FirstShip>();
+        }
+
+        respawn_timer.0.tick(time.delta());
+        if respawn_timer.0.finished() {
+            let ship_id = selected_ship.map_or(ships_data.player_ship, |selected| selected.0);
+            spawn_player_ship_events.write(player_spawn_event(client_entity, client_id, ship_id));
+            respawn_timer.0.reset();
+        }
+    }
+}
+
+fn player_spawn_event(client_entity: Entity, client_id: PeerId, ship_id: ShipId) -> SpawnPlayerShipEvent {
+    SpawnPlayerShipEvent {
+        client_entity,
+        client_id,
+        ship_id,
     }
 }
 
@@ -57,10 +226,21 @@ fn spawn_player_ship_system(
     mut events: EventReader<SpawnPlayerShipEvent>,
     weapons_data: Res<WeaponsData>,
     ships_data: Res<ShipsData>,
+    ship_colliders: Res<ShipColliders>,
+    bot_settings: Res<BotSettings>,
     existing_player_ships: Query<(Entity, &UniqueIdentity), With<PlayerShip>>,
+    teams: Query<&Team, With<ClientOf>>,
+    spawn_points: SpawnPoints,
 ) {
+    // keep the players that spawn together apart
+    let mut spawned = Vec::new();
     for event in events.read() {
         if let Some(ship_data) = ships_data.ships.get(&event.ship_id) {
+            let Some(collider) = ship_colliders.get(event.ship_id) else {
+                // the respawn timer will try again
+                warn!("Collider for ship id {} is not ready yet", event.ship_id);
+                continue;
+            };
             let existing_player_ship = existing_player_ships.iter().find(|(_, identity)| match identity {
                 UniqueIdentity::Player(client_id) => *client_id == event.client_id,
                 _ => false,
@@ -71,7 +251,9 @@ fn spawn_player_ship_system(
                 commands.entity(entity).despawn();
             }
 
-            commands.spawn(
+            let (position, rotation) = spawn_points.pick(&spawned, (FALLBACK_SPAWN_POSITION, Quat::from_rotation_arc(Vec3::Y, Vec3::Z)));
+            spawned.push(position);
+            let ship = commands.spawn(
                 (
                     Name::from(format!("Player Ship ({})", event.client_id)),
                     Replicate::to_clients(NetworkTarget::All),
@@ -79,23 +261,36 @@ fn spawn_player_ship_system(
                     InterpolationTarget::to_clients(NetworkTarget::AllExceptSingle(event.client_id)),
                     ControlledBy {
                         owner: event.client_entity,
-                        lifetime: Default::default()
+                        // keep the ship around when the client disconnects so that a bot can take over
+                        lifetime: if bot_settings.possess_disconnected {
+                            Lifetime::Persistent
+                        } else {
+                            Default::default()
+                        },
                     },
                     // in case the renderer is enabled on the server, we don't want the visuals to be replicated!
                     DisableReplicateHierarchy,
                     UniqueIdentity::Player(event.client_id),
                     PlayerShip,
-                    Ship(0),
+                    Ship(event.ship_id),
                     Damageable {
                         health: ship_data.starting_health,
                     },
+                    Energy::new(&ship_data.energy),
+                    HeldKeys::default(),
+                    FlightMode::default(),
+                    Dash::default(),
                     CurrentWeaponIndex(0),
-                    WeaponInventory::from_data(&weapons_data, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]),
-                    Position::from(event.position),
-                    Rotation::from(event.rotation),
-                    get_shared_ship_components(Collider::sphere(0.5))
+                    WeaponInventory::from_data(&weapons_data, ship_data.loadout.clone()),
+                    Position::from(position),
+                    Rotation::from(rotation),
+                    get_shared_ship_components(collider, &ship_data.physics)
                 )
-            );
+            ).id();
+            // the bots use the team of the ship to tell friends from foes
+            if let Ok(team) = teams.get(event.client_entity) {
+                commands.entity(ship).insert(*team);
+            }
         } else {
             error!("Ship data not found for ship id: {}", event.ship_id);
         }
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use lightyear::prelude::*;
+use lightyear::prelude::server::ClientOf;
+use shared::player::{PlayerShip, ServerControlled};
+use shared::prelude::{PlayerInput, UniqueIdentity};
+
+use crate::bot::{BotBrain, BotPerception, BotSettings, BotSkill, BotTarget};
+use crate::player::DuplicateClient;
+
+/// Lets a bot fly the ship of a disconnected player until the player reconnects.
+/// The ship entity is kept as is, so the player gets back their health, inventory and score.
+///
+/// Only enabled if [`BotSettings::possess_disconnected`] is set.
+pub(crate) struct PossessionPlugin;
+
+impl Plugin for PossessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(possess_on_disconnect);
+        app.add_systems(Update, possession_timeout_system);
+    }
+}
+
+/// A player ship that is flown by a bot while its player is away.
+#[derive(Component, Debug)]
+pub(crate) struct Possessed {
+    /// the player that owns the ship
+    pub(crate) client_id: PeerId,
+    /// if the player did not reconnect by the time this finishes, the ship is removed
+    grace_timer: Timer,
+}
+
+/// When a client disconnects, hand its ship over to a bot
+fn possess_on_disconnect(
+    trigger: Trigger<OnAdd, Disconnected>,
+    mut commands: Commands,
+    settings: Res<BotSettings>,
+    // the duplicates never got a ship, their disconnection must not affect the player already using the id
+    clients: Query<&RemoteId, (With<ClientOf>, Without<DuplicateClient>)>,
+    ships: Query<(Entity, &UniqueIdentity), (With<PlayerShip>, Without<Possessed>)>,
+) {
+    if !settings.possess_disconnected {
+        return;
+    }
+    let Ok(remote_id) = clients.get(trigger.target()) else { return };
+    let client_id = remote_id.0;
+
+    let Some((ship, _)) = ships.iter().find(|(_, identity)| **identity == UniqueIdentity::Player(client_id)) else {
+        return;
+    };
+
+    info!("Client {client_id:?} disconnected, a bot takes over their ship {ship}");
+    commands.entity(ship)
+        .remove::<ControlledBy>()
+        .insert((
+            ServerControlled,
+            BotBrain::default(),
+            BotSkill(settings.difficulty.profile()),
+            // drop whatever the player was pressing when they disconnected
+            ActionState::<PlayerInput>::default(),
+            Possessed {
+                client_id,
+                grace_timer: Timer::new(settings.reconnect_grace_period, TimerMode::Once),
+            },
+        ));
+}
+
+/// Give control of a possessed ship back to its player
+pub(crate) fn release_possession(commands: &mut Commands, ship: Entity, client_entity: Entity) {
+    commands.entity(ship)
+        .remove::<(ServerControlled, BotBrain, BotPerception, BotSkill, BotTarget, Possessed)>()
+        .insert((
+            ActionState::<PlayerInput>::default(),
+            ControlledBy {
+                owner: client_entity,
+                lifetime: Lifetime::Persistent,
+            },
+        ));
+}
+
+/// Remove the ships of players that did not come back in time
+fn possession_timeout_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut possessed: Query<(Entity, &mut Possessed)>,
+) {
+    for (ship, mut possessed) in possessed.iter_mut() {
+        possessed.grace_timer.tick(time.delta());
+        if possessed.grace_timer.finished() {
+            info!("Client {:?} did not reconnect in time, removing their ship {}", possessed.client_id, ship);
+            commands.entity(ship).despawn();
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use lightyear::prelude::server::*;
+use shared::bot::BotShip;
+use shared::player::PlayerShip;
+use shared::prelude::{DamageDealtEvent, GameMode, Team, KillFeedChannel, KillFeedEntry, Scoreboard, ShipDestroyedEvent, UniqueIdentity, WeaponFiredEvent, WeaponsData};
+
+use crate::bot::BotSettings;
+use crate::player::{DuplicateClient, PlayerMessageSender};
+use crate::possession::Possessed;
+
+/// Keeps the score of every player and bot, and tells everyone who shot down whom
+pub(crate) struct ScoreboardPlugin;
+
+impl Plugin for ScoreboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RecentDamage>();
+        app.add_observer(spawn_scoreboard_system);
+        app.add_observer(add_score_system);
+        app.add_observer(team_changed_system);
+        app.add_observer(player_left_system);
+        app.add_observer(possession_ended_system);
+        app.add_observer(weapon_fired_system);
+        app.add_observer(damage_dealt_system);
+        app.add_observer(ship_destroyed_system);
+    }
+}
+
+/// How long (in seconds) damaging a ship counts towards an assist
+const ASSIST_WINDOW_SECS: f32 = 10.0;
+
+/// Who damaged each ship recently, and when (in seconds since startup). Used to award assists.
+#[derive(Resource, Debug, Default)]
+struct RecentDamage(HashMap<UniqueIdentity, Vec<(UniqueIdentity, f32)>>);
+
+/// The scoreboard lives as long as the server, it is despawned with the other replicated entities when it stops
+fn spawn_scoreboard_system(
+    _: Trigger<OnAdd, Started>,
+    mut commands: Commands,
+    mut recent_damage: ResMut<RecentDamage>,
+) {
+    recent_damage.0.clear();
+    commands.spawn((
+        Name::from("Scoreboard"),
+        Scoreboard::default(),
+        Replicate::to_clients(NetworkTarget::All),
+    ));
+}
+
+/// Players show up on the scoreboard as soon as they are accepted, bots when their ship spawns.
+/// Only the identities on the scoreboard are scored, the co-op enemies are not.
+fn add_score_system(
+    trigger: Trigger<OnAdd, UniqueIdentity>,
+    game_mode: Res<GameMode>,
+    identities: Query<(&UniqueIdentity, Option<&Team>, Has<BotShip>), Or<(With<ClientOf>, With<BotShip>)>>,
+    mut scoreboard: Query<&mut Scoreboard>,
+) {
+    let Ok((identity, team, is_bot)) = identities.get(trigger.target()) else { return };
+    if is_bot && *game_mode == GameMode::Coop {
+        return;
+    }
+    let Ok(mut scoreboard) = scoreboard.single_mut() else { return };
+    scoreboard.get_or_insert(*identity).team = team.copied();
+}
+
+fn team_changed_system(
+    trigger: Trigger<OnInsert, Team>,
+    identities: Query<(&UniqueIdentity, &Team), Or<(With<ClientOf>, With<BotShip>)>>,
+    mut scoreboard: Query<&mut Scoreboard>,
+) {
+    let Ok((identity, team)) = identities.get(trigger.target()) else { return };
+    let Ok(mut scoreboard) = scoreboard.single_mut() else { return };
+    if let Some(score) = scoreboard.get_mut(identity) {
+        score.team = Some(*team);
+    }
+}
+
+/// Bots that are removed keep their line until the server stops, players that leave don't.
+/// A player whose ship is taken over by a bot keeps their line while the bot flies it, the bot scores for them.
+fn player_left_system(
+    trigger: Trigger<OnAdd, Disconnected>,
+    bot_settings: Res<BotSettings>,
+    clients: Query<&UniqueIdentity, (With<ClientOf>, Without<DuplicateClient>)>,
+    player_ships: Query<&UniqueIdentity, With<PlayerShip>>,
+    mut scoreboard: Query<&mut Scoreboard>,
+) {
+    let Ok(identity) = clients.get(trigger.target()) else { return };
+    // the ship is about to be possessed, the line is removed once the possession ends without the player
+    if bot_settings.possess_disconnected && player_ships.iter().any(|ship| ship == identity) {
+        return;
+    }
+    let Ok(mut scoreboard) = scoreboard.single_mut() else { return };
+    scoreboard.remove(identity);
+}
+
+/// The possessed ship was removed before its player came back
+fn possession_ended_system(
+    trigger: Trigger<OnRemove, Possessed>,
+    possessed: Query<&Possessed>,
+    clients: Query<&RemoteId, (With<ClientOf>, With<Connected>, Without<DuplicateClient>)>,
+    mut scoreboard: Query<&mut Scoreboard>,
+) {
+    let Ok(possessed) = possessed.get(trigger.target()) else { return };
+    // the player reconnected and flies their ship again
+    if clients.iter().any(|remote_id| remote_id.0 == possessed.client_id) {
+        return;
+    }
+    let Ok(mut scoreboard) = scoreboard.single_mut() else { return };
+    scoreboard.remove(&UniqueIdentity::Player(possessed.client_id));
+}
+
+fn weapon_fired_system(
+    trigger: Trigger<WeaponFiredEvent>,
+    weapons_data: Option<Res<WeaponsData>>,
+    mut scoreboard: Query<&mut Scoreboard>,
+) {
+    let event = trigger.event();
+    let Some(weapon) = weapons_data.as_ref().and_then(|data| data.weapons.get(&event.weapon_index)) else { return };
+    let Ok(mut scoreboard) = scoreboard.single_mut() else { return };
+    // every barrel fires its own projectile
+    if let Some(score) = scoreboard.get_mut(&event.shooter_id) {
+        score.shots_fired += weapon.barrel_positions.len() as u32;
+    }
+}
+
+fn damage_dealt_system(
+    trigger: Trigger<DamageDealtEvent>,
+    time: Res<Time>,
+    mut recent_damage: ResMut<RecentDamage>,
+    mut scoreboard: Query<&mut Scoreboard>,
+) {
+    let event = trigger.event();
+    let Ok(mut scoreboard) = scoreboard.single_mut() else { return };
+    if let Some(score) = scoreboard.get_mut(&event.attacker) {
+        score.damage_dealt += event.damage as u32;
+        score.shots_hit += 1;
+    }
+
+    if event.attacker != event.victim {
+        let now = time.elapsed_secs();
+        let attackers = recent_damage.0.entry(event.victim).or_default();
+        attackers.retain(|(_, damaged_at)| now - *damaged_at <= ASSIST_WINDOW_SECS);
+        attackers.push((event.attacker, now));
+    }
+}
+
+fn ship_destroyed_system(
+    trigger: Trigger<ShipDestroyedEvent>,
+    time: Res<Time>,
+    mut commands: Commands,
+    mut recent_damage: ResMut<RecentDamage>,
+    mut scoreboard: Query<&mut Scoreboard>,
+    mut messages: PlayerMessageSender,
+) {
+    let event = trigger.event();
+    let attackers = recent_damage.0.remove(&event.victim).unwrap_or_default();
+
+    if let Ok(mut scoreboard) = scoreboard.single_mut() {
+        if let Some(score) = scoreboard.get_mut(&event.victim) {
+            score.deaths += 1;
+        }
+        if let Some(score) = event.killer.filter(|killer| *killer != event.victim).and_then(|killer| scoreboard.get_mut(&killer)) {
+            score.kills += 1;
+        }
+        // everyone else who recently damaged the ship helped, once per ship
+        let now = time.elapsed_secs();
+        let mut assisted = Vec::new();
+        for (attacker, damaged_at) in attackers {
+            if now - damaged_at > ASSIST_WINDOW_SECS || Some(attacker) == event.killer || assisted.contains(&attacker) {
+                continue;
+            }
+            assisted.push(attacker);
+            if let Some(score) = scoreboard.get_mut(&attacker) {
+                score.assists += 1;
+            }
+        }
+    }
+
+    let entry = KillFeedEntry {
+        killer: event.killer,
+        victim: event.victim,
+        weapon_index: event.weapon_index,
+    };
+    // the host-client sees the entry triggered by the server
+    commands.trigger(entry.clone());
+    messages.broadcast::<KillFeedEntry, KillFeedChannel>(&entry);
+}
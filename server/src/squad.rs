@@ -0,0 +1,180 @@
+use core::f32::consts::PI;
+
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use shared::bot::BotShip;
+
+use crate::bot::{BotMemory, BotPerception, BotSet, BotTarget};
+
+/// Groups bots into squads so that they fight together instead of independently.
+/// - squad members share what they see, so the whole squad converges on the same target
+/// - each member gets a role that changes the distance it keeps from the target
+/// - members orbit the target in different planes, spread evenly around it
+/// - members take turns shooting
+pub(crate) struct SquadPlugin;
+
+impl Plugin for SquadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Squads>();
+        app.add_systems(FixedUpdate, (
+            assign_squads_system,
+            share_targets_system,
+        ).chain().after(BotSet::Perceive).before(BotSet::Move));
+    }
+}
+
+/// The maximum number of bots in a squad
+const SQUAD_SIZE: usize = 3;
+
+/// The duration (in seconds) of a full firing cycle of a squad, every member gets to fire once per cycle
+const FIRE_CYCLE_SECS: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SquadRole {
+    /// Stays at the regular distance and keeps the target busy by firing most of the time
+    Suppressor,
+    /// Gets in close and orbits the target while the suppressor has its attention
+    Flanker,
+    /// Hangs back and only takes a few well-timed shots
+    Sniper,
+}
+
+impl SquadRole {
+    fn from_slot(slot: usize) -> Self {
+        match slot % 3 {
+            0 => SquadRole::Suppressor,
+            1 => SquadRole::Flanker,
+            _ => SquadRole::Sniper,
+        }
+    }
+
+    /// scales the distance the bot tries to keep from its target
+    pub(crate) fn distance_factor(&self) -> f32 {
+        match self {
+            SquadRole::Suppressor => 1.0,
+            SquadRole::Flanker => 0.6,
+            SquadRole::Sniper => 1.8,
+        }
+    }
+
+    /// the fraction of the firing cycle during which the bot is allowed to fire
+    fn fire_duty(&self) -> f32 {
+        match self {
+            SquadRole::Suppressor => 0.7,
+            SquadRole::Flanker => 0.4,
+            SquadRole::Sniper => 0.2,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub(crate) struct SquadMember {
+    pub(crate) squad: usize,
+    pub(crate) role: SquadRole,
+    /// the position of the bot inside its squad
+    pub(crate) slot: usize,
+    /// the number of bots in the squad
+    pub(crate) squad_size: usize,
+}
+
+impl SquadMember {
+    /// The direction the bot should orbit in around its target.
+    /// Each member of the squad orbits in its own plane, the planes are rotated evenly around the direction to the target.
+    pub(crate) fn orbit_dir(&self, to_target: Vec3, clockwise: bool) -> Vec3 {
+        let dir = to_target.normalize_or_zero();
+        // pick a reference axis that is not aligned with the direction to the target
+        let reference = if dir.dot(Vec3::Y).abs() > 0.99 { Vec3::X } else { Vec3::Y };
+        let base_normal = dir.cross(reference).normalize_or_zero();
+        // a plane and its flipped normal are the same plane, so we only need to spread them over half a turn
+        let angle = self.slot as f32 / self.squad_size.max(1) as f32 * PI;
+        let normal = Quat::from_axis_angle(dir, angle) * base_normal;
+        let orbit_dir = normal.cross(dir);
+        if clockwise { orbit_dir } else { -orbit_dir }
+    }
+
+    /// Whether it's this member's turn to fire.
+    /// The firing windows of the members are staggered over the firing cycle.
+    pub(crate) fn can_fire(&self, elapsed_secs: f32) -> bool {
+        let offset = self.slot as f32 / self.squad_size.max(1) as f32;
+        let phase = (elapsed_secs / FIRE_CYCLE_SECS - offset).rem_euclid(1.0);
+        phase < self.role.fire_duty()
+    }
+}
+
+#[derive(Resource, Default)]
+struct Squads {
+    squads: Vec<Vec<Entity>>,
+}
+
+/// Put new bots in squads and remove dead ones.
+/// Whenever a squad changes, the roles and slots of its members are reassigned.
+fn assign_squads_system(
+    mut commands: Commands,
+    mut squads: ResMut<Squads>,
+    mut removed_bots: RemovedComponents<BotShip>,
+    new_bots: Query<Entity, (With<BotShip>, With<BotPerception>, Without<SquadMember>)>,
+) {
+    let mut dirty = false;
+
+    for entity in removed_bots.read() {
+        for squad in squads.squads.iter_mut() {
+            squad.retain(|member| *member != entity);
+        }
+        dirty = true;
+    }
+    squads.squads.retain(|squad| !squad.is_empty());
+
+    for entity in new_bots.iter() {
+        if squads.squads.iter().any(|squad| squad.contains(&entity)) {
+            continue;
+        }
+        if let Some(squad) = squads.squads.iter_mut().find(|squad| squad.len() < SQUAD_SIZE) {
+            squad.push(entity);
+        } else {
+            squads.squads.push(vec![entity]);
+        }
+        dirty = true;
+    }
+
+    if dirty {
+        for (squad_index, squad) in squads.squads.iter().enumerate() {
+            for (slot, entity) in squad.iter().enumerate() {
+                commands.entity(*entity).try_insert(SquadMember {
+                    squad: squad_index,
+                    role: SquadRole::from_slot(slot),
+                    slot,
+                    squad_size: squad.len(),
+                });
+            }
+        }
+    }
+}
+
+/// Squad members that don't see anything go after the target spotted by one of their squadmates.
+fn share_targets_system(
+    mut commands: Commands,
+    mut bots: Query<(Entity, &SquadMember, &mut BotPerception)>,
+) {
+    let mut spotted = HashMap::<usize, (Entity, Vec3)>::default();
+    for (_, member, perception) in bots.iter() {
+        if let Some(memory) = perception.memory.as_ref().filter(|memory| memory.in_sight) {
+            spotted.entry(member.squad).or_insert((memory.entity, memory.last_known_position));
+        }
+    }
+
+    for (bot_entity, member, mut perception) in bots.iter_mut() {
+        let Some((target, position)) = spotted.get(&member.squad).copied() else { continue };
+        if perception.memory.as_ref().is_some_and(|memory| memory.in_sight) {
+            continue;
+        }
+        if perception.memory.as_ref().is_none_or(|memory| memory.entity != target) {
+            commands.entity(bot_entity).insert(BotTarget::new(target));
+        }
+        perception.memory = Some(BotMemory {
+            entity: target,
+            last_known_position: position,
+            in_sight: false,
+            time_since_seen: 0.0,
+        });
+    }
+}
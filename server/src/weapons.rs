@@ -1,13 +1,16 @@
 use avian3d::prelude::{LinearVelocity, PhysicsStepSet, Position, Rotation, SpatialQueryFilter};
 use bevy::math::NormedVectorSpace;
 use bevy::prelude::*;
-use shared::{prelude::{Damageable, PlayerInput, UniqueIdentity}, weapons::{handle_shooting, CurrentWeaponIndex, ProjectileHitEvent, WeaponInventory, WeaponsData}};
+use shared::{prelude::{DamageDealtEvent, DamageTaken, DamageTakenChannel, DamageType, Damageable, HitConfirmed, HitConfirmedChannel, PlayerInput, Ship, ShipDestroyedEvent, UniqueIdentity}, weapons::{handle_shooting, CurrentWeaponIndex, ProjectileHitEvent, WeaponInventory, WeaponsData}};
 use leafwing_input_manager::prelude::ActionState;
 use lightyear::prelude::*;
 use lightyear::prelude::server::ClientOf;
 use lightyear_avian3d::prelude::LagCompensationSpatialQuery;
 use shared::prelude::{GameLayer, Projectile, ProjectileInfo, WeaponsSet};
 
+use crate::game_mode::DamageRules;
+use crate::player::PlayerMessageSender;
+
 /// Handles projectiles colliding with walls and enemies
 pub(crate) struct WeaponsPlugin;
 
@@ -68,16 +71,68 @@ fn projectile_hit_system(
     mut commands: Commands,
     mut events: EventReader<ProjectileHitEvent>,
     mut weapons_data: ResMut<WeaponsData>,
-    mut damageables: Query<&mut Damageable>,
+    mut damageables: Query<(&mut Damageable, Option<&UniqueIdentity>)>,
+    ships: Query<(&UniqueIdentity, &Position), With<Ship>>,
+    mut player_messages: PlayerMessageSender,
+    damage_rules: DamageRules,
 ) {
     for event in events.read() {
         // by this point the projectile itself has already been queued for despawn so we dont need to worry about the projectile itself
         if let Some(weapon_data) = weapons_data.weapons.get_mut(&event.weapon_index) {
             // @todo-brian: apply splash damage
             if let Some(entity_hit) = event.entity_hit {
-                if let Ok(mut damageable) = damageables.get_mut(entity_hit) {
+                if let Ok((mut damageable, identity)) = damageables.get_mut(entity_hit) {
+                    // several projectiles can hit in the same frame, only the first one that brings it to 0 kills it
+                    if damageable.health == 0 {
+                        continue;
+                    }
+                    // teammates, or the match is over
+                    if identity.is_some_and(|victim| !damage_rules.allows(event.shooter_id, *victim)) {
+                        continue;
+                    }
+                    let health_before = damageable.health;
                     damageable.health = damageable.health.saturating_sub(weapon_data.projectile.direct_damage);
-                    if damageable.health <= 0 {
+
+                    let damage = health_before - damageable.health;
+                    if let Some(victim) = identity {
+                        commands.trigger(DamageDealtEvent {
+                            attacker: event.shooter_id,
+                            victim: *victim,
+                            damage,
+                        });
+                    }
+
+                    // let the shooter know they hit something
+                    if let UniqueIdentity::Player(shooter) = event.shooter_id {
+                        player_messages.send::<_, HitConfirmedChannel>(shooter, HitConfirmed {
+                            damage,
+                            killed: damageable.health == 0,
+                            position: event.position,
+                        });
+                    }
+                    // and the victim where it came from
+                    if let Some(UniqueIdentity::Player(victim)) = identity {
+                        let attacker_position = ships.iter()
+                            .find(|(ship_identity, _)| **ship_identity == event.shooter_id)
+                            .map(|(_, position)| position.0)
+                            // the shooter is already gone, at least show where the projectile landed
+                            .unwrap_or(event.position);
+                        player_messages.send::<_, DamageTakenChannel>(*victim, DamageTaken {
+                            damage,
+                            damage_type: DamageType::Projectile { weapon_index: event.weapon_index },
+                            attacker_position,
+                        });
+                    }
+
+                    if damageable.health == 0 {
+                        if let Some(victim) = identity {
+                            commands.trigger(ShipDestroyedEvent {
+                                ship: entity_hit,
+                                victim: *victim,
+                                killer: Some(event.shooter_id),
+                                weapon_index: Some(event.weapon_index),
+                            });
+                        }
                         commands.entity(entity_hit).despawn();
                     }
                 }
@@ -122,6 +177,7 @@ fn bullet_hit_detection(
                 weapon_index: projectile_info.weapon_index,
                 projectile_entity: bullet_entity,
                 entity_hit: Some(hit.entity),
+                position: current_pos.0 + current_velocity.0.normalize() * hit.distance,
             };
             info!(?tick, "Sending bullet hit event: {:?}", hit_event);
             hit_events.write(hit_event);
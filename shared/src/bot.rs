@@ -1,10 +1,11 @@
+use core::str::FromStr;
+
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
+/// Marker for ships that are flown by bots
 #[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct BotShip {
-    pub wish_dir: Vec3,
-}
+pub struct BotShip;
 
 #[derive(Debug, Deserialize)]
 pub struct BotBehavior {
@@ -19,6 +20,9 @@ pub struct BotBehavior {
 
     /// the kind of attack the bot will use
     pub attack_kind: BotAttackKind,
+
+    /// how the bot sees, hears and remembers its targets
+    pub perception: BotPerceptionBehavior,
 }
 
 impl Default for BotBehavior {
@@ -30,6 +34,37 @@ impl Default for BotBehavior {
             attack_kind: BotAttackKind::Standard { 
                 target_distance: 10.0,
             },
+            perception: BotPerceptionBehavior::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BotPerceptionBehavior {
+    /// the full angle (in degrees) of the cone in front of the bot in which it can spot players
+    pub field_of_view_degrees: f32,
+
+    /// the maximum distance at which the bot can spot players
+    pub sight_range: f32,
+
+    /// the distance at which the bot hears weapons being fired and projectiles hitting
+    pub hearing_range: f32,
+
+    /// how long (in seconds) the bot remembers the last known position of a target it can no longer see
+    pub memory_duration: f32,
+
+    /// once the bot gets this close to the last known position of its target, it gives up the search
+    pub search_radius: f32,
+}
+
+impl Default for BotPerceptionBehavior {
+    fn default() -> Self {
+        Self {
+            field_of_view_degrees: 120.0,
+            sight_range: 60.0,
+            hearing_range: 30.0,
+            memory_duration: 5.0,
+            search_radius: 2.0,
         }
     }
 }
@@ -63,3 +98,80 @@ pub enum BotAttackKind {
         wish_dir_random_factor: f32,
     }
 }
+
+/// How good the bots are. Each difficulty maps to a [`BotDifficultyProfile`] that scales the [`BotBehavior`] of the ship they fly.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BotDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl BotDifficulty {
+    pub fn profile(&self) -> BotDifficultyProfile {
+        match self {
+            BotDifficulty::Easy => BotDifficultyProfile {
+                reaction_time: 0.8,
+                accuracy: 0.4,
+                aggression: 0.75,
+            },
+            BotDifficulty::Normal => BotDifficultyProfile {
+                reaction_time: 0.4,
+                accuracy: 0.7,
+                aggression: 1.0,
+            },
+            BotDifficulty::Hard => BotDifficultyProfile {
+                reaction_time: 0.15,
+                accuracy: 0.9,
+                aggression: 1.3,
+            },
+        }
+    }
+}
+
+impl FromStr for BotDifficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "easy" => Ok(BotDifficulty::Easy),
+            "normal" => Ok(BotDifficulty::Normal),
+            "hard" => Ok(BotDifficulty::Hard),
+            _ => Err(format!("unknown bot difficulty '{s}', expected one of: easy, normal, hard")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BotDifficultyProfile {
+    /// the time (in seconds) it takes the bot to react to a newly acquired target before it starts shooting
+    pub reaction_time: f32,
+
+    /// how well the bot aims, between 0.0 (wild spray) and 1.0 (perfect aim)
+    pub accuracy: f32,
+
+    /// scales how close the bot is willing to get to its target and how often it changes its orbit,
+    /// 1.0 keeps the values of the BotBehavior untouched
+    pub aggression: f32,
+}
+
+impl BotDifficultyProfile {
+    /// the maximum angle (in radians) the bot's aim can be off by with an accuracy of 0.0
+    pub const MAX_AIM_ERROR: f32 = 0.2;
+
+    /// scale a distance the bot keeps from its target, aggressive bots get closer
+    pub fn scale_distance(&self, distance: f32) -> f32 {
+        distance / self.aggression.max(0.01)
+    }
+
+    /// scale an interval between two bot decisions, aggressive bots change their mind more often
+    pub fn scale_interval(&self, interval: f32) -> f32 {
+        interval / self.aggression.max(0.01)
+    }
+
+    /// the maximum angle (in radians) the bot's aim can be off by
+    pub fn aim_error(&self) -> f32 {
+        (1.0 - self.accuracy.clamp(0.0, 1.0)) * Self::MAX_AIM_ERROR
+    }
+}
@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The longest message (in characters) the server accepts
+pub const MAX_CHAT_MESSAGE_LEN: usize = 200;
+
+/// Who can read a chat message
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatScope {
+    /// Everyone on the server
+    #[default]
+    All,
+    /// Only the players of the author's [`Team`](crate::game_mode::Team)
+    Team,
+}
+
+/// A line of text chat.
+/// Clients send it to the server to say something, the server checks it and forwards it to the clients
+/// that should read it, along with its own messages about joins, leaves and kills.
+/// On the clients, it is triggered once received so that the chat box can show it.
+#[derive(Event, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// The name of the player who wrote the message, `None` for messages from the server itself.
+    /// The server fills it in, whatever the client puts there is ignored.
+    pub author: Option<String>,
+    pub scope: ChatScope,
+    pub text: String,
+}
+
+impl ChatMessage {
+    /// A message from the server, read by everyone
+    pub fn system(text: impl Into<String>) -> Self {
+        Self {
+            author: None,
+            scope: ChatScope::All,
+            text: text.into(),
+        }
+    }
+}
+
+/// Triggered by the chat box when the local player sends a message, the client forwards it to the server
+#[derive(Event, Debug, Clone)]
+pub struct SendChatMessage {
+    pub scope: ChatScope,
+    pub text: String,
+}
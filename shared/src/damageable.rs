@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::identity::UniqueIdentity;
+
 /// A component that defines the damageable properties of an entity.
 /// Apply this to entities that should take damage.
 // this is in shared and replicated
@@ -9,3 +11,42 @@ pub struct Damageable {
     /// Kept as a u16 to keep the network payload small, if you need more health capacity than u16::MAX (lol), consider lowering damages!
     pub health: u16,
 }
+
+/// Triggered on the server when a ship's health reaches 0, right before it is despawned
+#[derive(Event, Debug, Clone)]
+pub struct ShipDestroyedEvent {
+    pub ship: Entity,
+    pub victim: UniqueIdentity,
+    /// Who shot the ship down, `None` if it crashed into something
+    pub killer: Option<UniqueIdentity>,
+    /// The weapon of the killer
+    pub weapon_index: Option<u32>,
+}
+
+/// Triggered on the server when a projectile damages a ship
+#[derive(Event, Debug, Clone)]
+pub struct DamageDealtEvent {
+    pub attacker: UniqueIdentity,
+    pub victim: UniqueIdentity,
+    /// The health the victim actually lost
+    pub damage: u16,
+}
+
+/// What hurt a ship
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DamageType {
+    /// Shot with the weapon at this index in the `WeaponsData`
+    Projectile { weapon_index: u32 },
+    /// Slammed into a wall or another ship
+    Collision,
+}
+
+/// Sent by the server to a player whenever their ship takes damage, so that the HUD can show where it came from.
+/// Triggered on the victim's client once received.
+#[derive(Event, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DamageTaken {
+    pub damage: u16,
+    pub damage_type: DamageType,
+    /// Where the shooter was when the projectile hit, or the point the ship slammed into
+    pub attacker_position: Vec3,
+}
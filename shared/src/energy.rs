@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ships::{Ship, ShipsData};
+
+pub struct EnergyPlugin;
+
+impl Plugin for EnergyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, regenerate_energy_system
+            .after(crate::player::move_player)
+            .run_if(resource_exists::<ShipsData>));
+    }
+}
+
+/// The energy pool of a ship, per ship values are defined in the ship's [`EnergyBehavior`].
+#[derive(TypePath, Debug, Clone, Deserialize)]
+pub struct EnergyBehavior {
+    pub capacity: f32,
+    /// Energy regained per second
+    pub regen_rate: f32,
+    /// Time (in seconds) after energy was last spent before it starts regenerating
+    pub regen_delay: f32,
+    /// Energy drained per second while the afterburners are on
+    pub afterburner_drain: f32,
+}
+
+impl Default for EnergyBehavior {
+    fn default() -> Self {
+        Self {
+            capacity: 100.0,
+            regen_rate: 20.0,
+            regen_delay: 1.0,
+            afterburner_drain: 25.0,
+        }
+    }
+}
+
+/// The energy a ship has left to spend on afterburners, weapons and abilities.
+/// This is predicted, so it must only be modified in FixedUpdate.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Energy {
+    pub current: f32,
+    /// Time (in seconds) left before the energy starts regenerating
+    pub regen_cooldown: f32,
+}
+
+impl Energy {
+    pub fn new(behavior: &EnergyBehavior) -> Self {
+        Self {
+            current: behavior.capacity,
+            regen_cooldown: 0.0,
+        }
+    }
+
+    /// Spend `amount` energy. Nothing is spent if there isn't enough energy left.
+    /// Returns true if the energy was spent.
+    pub fn try_drain(&mut self, amount: f32, behavior: &EnergyBehavior) -> bool {
+        if self.current < amount {
+            return false;
+        }
+        self.current -= amount;
+        self.regen_cooldown = behavior.regen_delay;
+        true
+    }
+
+    pub fn regenerate(&mut self, behavior: &EnergyBehavior, delta_seconds: f32) {
+        if self.regen_cooldown > 0.0 {
+            self.regen_cooldown = (self.regen_cooldown - delta_seconds).max(0.0);
+            return;
+        }
+        self.current = (self.current + behavior.regen_rate * delta_seconds).min(behavior.capacity);
+    }
+
+    /// The fraction of the energy pool that is left, between 0.0 and 1.0
+    pub fn fraction(&self, behavior: &EnergyBehavior) -> f32 {
+        if behavior.capacity <= 0.0 {
+            return 0.0;
+        }
+        (self.current / behavior.capacity).clamp(0.0, 1.0)
+    }
+}
+
+fn regenerate_energy_system(
+    fixed_time: Res<Time<Fixed>>,
+    ships_data: Res<ShipsData>,
+    mut query: Query<(&Ship, &mut Energy), Or<(With<Predicted>, With<Replicating>)>>,
+) {
+    for (ship, mut energy) in query.iter_mut() {
+        if let Some(data) = ships_data.ships.get(&ship.0) {
+            energy.regenerate(&data.energy, fixed_time.delta_secs());
+        }
+    }
+}
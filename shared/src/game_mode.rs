@@ -0,0 +1,146 @@
+use core::str::FromStr;
+
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::identity::UniqueIdentity;
+use crate::keys::KeyColor;
+use crate::ships::ShipId;
+
+/// The rules of the match running on the server
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Deathmatch,
+    TeamDeathmatch,
+    Coop,
+}
+
+impl GameMode {
+    /// Whether the players are split in teams
+    pub fn has_teams(&self) -> bool {
+        matches!(self, GameMode::TeamDeathmatch | GameMode::Coop)
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dm" | "deathmatch" => Ok(GameMode::Deathmatch),
+            "tdm" | "teamdeathmatch" => Ok(GameMode::TeamDeathmatch),
+            "coop" => Ok(GameMode::Coop),
+            _ => Err(format!("unknown game mode '{s}', expected one of: dm, tdm, coop")),
+        }
+    }
+}
+
+/// The side a player fights for in team games.
+/// Lives on the client entity of the players on the server, and on the ships.
+/// In co-op the players are all blue and the enemies red.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Team {
+    Red,
+    Blue,
+}
+
+/// How the rules of a game mode are tuned, defined in `data/game_modes.ron`
+#[derive(TypePath, Debug, Clone, Deserialize)]
+pub struct GameModeRules {
+    /// Time (in seconds) to join and warm up before the match starts
+    pub warmup_secs: f32,
+    /// The match ends after this time (in seconds)
+    pub time_limit_secs: Option<f32>,
+    /// The match ends when a player (or a team) reaches this number of kills.
+    /// Co-op ends once every enemy is destroyed.
+    pub score_limit: Option<u32>,
+    /// Time (in seconds) the results are shown before the next match
+    pub post_match_secs: f32,
+    /// Whether players can damage their teammates
+    pub friendly_fire: bool,
+}
+
+impl Default for GameModeRules {
+    fn default() -> Self {
+        Self {
+            warmup_secs: 15.0,
+            time_limit_secs: Some(600.0),
+            score_limit: Some(20),
+            post_match_secs: 10.0,
+            friendly_fire: false,
+        }
+    }
+}
+
+/// The kinds of enemies placed in the maps for co-op, one entity class each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EnemyKind {
+    Laser,
+    Plasma,
+    Fusion,
+}
+
+#[derive(Asset, Resource, Default, TypePath, Debug, Deserialize)]
+pub struct GameModesData {
+    /// Game modes that are missing use the default rules
+    pub rules: HashMap<GameMode, GameModeRules>,
+    /// The ship flown by each kind of enemy in co-op
+    pub enemy_ships: HashMap<EnemyKind, ShipId>,
+}
+
+impl GameModesData {
+    pub fn rules(&self, game_mode: GameMode) -> GameModeRules {
+        self.rules.get(&game_mode).cloned().unwrap_or_default()
+    }
+}
+
+/// Where the match is at
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchPhase {
+    /// Players join and fly around, nothing counts yet
+    #[default]
+    Warmup,
+    InProgress,
+    /// The results are shown, nobody can be damaged
+    PostMatch,
+}
+
+/// How the last match ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchResult {
+    Winner(UniqueIdentity),
+    WinningTeam(Team),
+    Draw,
+    /// Co-op: every enemy was destroyed
+    Victory,
+    /// Co-op: the time ran out
+    Defeat,
+}
+
+/// The state of the match, so that the clients can show a timer and the results.
+/// Lives on a single entity spawned by the server and replicated to every client.
+#[derive(Component, Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchState {
+    pub game_mode: GameMode,
+    pub phase: MatchPhase,
+    /// Whole seconds left in the current phase, `None` if the phase doesn't end on its own
+    pub time_left: Option<u32>,
+    pub score_limit: Option<u32>,
+    /// Kills of each team in team deathmatch
+    pub red_score: u32,
+    pub blue_score: u32,
+    /// Co-op: the enemies still alive
+    pub enemies_left: u32,
+    /// Set when the match ends
+    pub result: Option<MatchResult>,
+}
+
+/// A key waiting to be picked up in co-op.
+/// Spawned by the server where the map places keys, and replicated so that the clients can show it.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyPickup {
+    pub color: KeyColor,
+    pub position: Vec3,
+}
@@ -1,3 +1,5 @@
+use core::fmt;
+
 use bevy::ecs::component::Component;
 use lightyear::prelude::PeerId;
 use serde::{Deserialize, Serialize};
@@ -9,3 +11,13 @@ pub enum UniqueIdentity {
     Player(PeerId),
     Bot(u32),
 }
+
+/// How the identity is shown to the players, in the chat for example
+impl fmt::Display for UniqueIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UniqueIdentity::Player(peer_id) => write!(f, "Player ({peer_id})"),
+            UniqueIdentity::Bot(id) => write!(f, "Bot {id}"),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How much damage a ship takes when it slams into a wall or another ship.
+/// Only the speed along the contact normal counts, so scraping along a wall is harmless.
+#[derive(TypePath, Debug, Clone, Deserialize)]
+pub struct CollisionDamageBehavior {
+    /// Impacts slower than this (in m/s) don't do any damage
+    pub speed_threshold: f32,
+    /// Damage per m/s above the threshold
+    pub damage_multiplier: f32,
+}
+
+impl Default for CollisionDamageBehavior {
+    fn default() -> Self {
+        Self {
+            speed_threshold: 8.0,
+            damage_multiplier: 2.0,
+        }
+    }
+}
+
+impl CollisionDamageBehavior {
+    pub fn damage(&self, impact_speed: f32) -> u16 {
+        if impact_speed <= self.speed_threshold {
+            return 0;
+        }
+        ((impact_speed - self.speed_threshold) * self.damage_multiplier).round().min(u16::MAX as f32) as u16
+    }
+}
+
+/// The velocity of a ship right before the physics step, before the solver resolved its collisions.
+/// Used to know how hard the ship hit something.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct PreImpactVelocity(pub Vec3);
+
+/// Event that is sent when a ship slams into a wall or another ship hard enough to take damage.
+/// The server applies the damage, then triggers this event and sends it to the clients
+/// so they can shake the screen, play crunch sounds, etc.
+#[derive(Event, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShipImpactEvent {
+    /// The ship that took the impact
+    pub entity: Entity,
+    pub position: Vec3,
+    /// The speed (in m/s) along the contact normal at which the ship hit
+    pub impact_speed: f32,
+    pub damage: u16,
+}
+
+impl MapEntities for ShipImpactEvent {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.entity = entity_mapper.get_mapped(self.entity);
+    }
+}
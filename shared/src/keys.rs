@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The keys that open the locked doors of the co-op maps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyColor {
+    Red,
+    Blue,
+    Yellow,
+}
+
+impl KeyColor {
+    pub const ALL: [KeyColor; 3] = [KeyColor::Red, KeyColor::Blue, KeyColor::Yellow];
+}
+
+/// The keys a ship picked up, they are lost with the ship
+// this is in shared and replicated
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HeldKeys {
+    pub red: bool,
+    pub blue: bool,
+    pub yellow: bool,
+}
+
+impl HeldKeys {
+    pub fn has(&self, key: KeyColor) -> bool {
+        match key {
+            KeyColor::Red => self.red,
+            KeyColor::Blue => self.blue,
+            KeyColor::Yellow => self.yellow,
+        }
+    }
+
+    pub fn give(&mut self, key: KeyColor) {
+        match key {
+            KeyColor::Red => self.red = true,
+            KeyColor::Blue => self.blue = true,
+            KeyColor::Yellow => self.yellow = true,
+        }
+    }
+}
@@ -13,9 +13,15 @@ pub mod utils;
 pub mod damageable;
 pub mod data;
 pub mod ships;
+pub mod game_mode;
+pub mod energy;
+pub mod impacts;
+pub mod chat;
+pub mod keys;
+pub mod scoreboard;
 
 pub mod prelude {
-    pub use crate::network::{protocol::*};
+    pub use crate::network::{protocol::*, DEFAULT_PORT};
     pub use crate::physics::*;
     pub use crate::weapons::*;
     pub use crate::map::*;
@@ -27,6 +33,12 @@ pub mod prelude {
     pub use crate::data::audio::*;
     pub use crate::data::weapons::*;
     pub use crate::ships::*;
+    pub use crate::game_mode::*;
+    pub use crate::energy::*;
+    pub use crate::impacts::*;
+    pub use crate::chat::*;
+    pub use crate::keys::*;
+    pub use crate::scoreboard::*;
 }
 
 #[derive(Clone, Default)]
@@ -42,6 +54,7 @@ impl Plugin for SharedPlugin {
         app.add_plugins(physics::PhysicsPlugin);
         app.add_plugins(ships::ShipPlugin);
         app.add_plugins(player::PlayerPlugin);
+        app.add_plugins(energy::EnergyPlugin);
         app.add_plugins(weapons::WeaponsPlugin);
         app.add_plugins(utils::UtilsPlugin);
     }
@@ -6,7 +6,10 @@ use bevy::ecs::world::DeferredWorld;
 use bevy::prelude::*;
 use bevy_trenchbroom::prelude::*;
 use pathnodes::{PathfindingGraph};
+use serde::{Deserialize, Serialize};
 
+use crate::game_mode::EnemyKind;
+use crate::keys::KeyColor;
 use crate::physics::GameLayer;
 
 #[solid_class]
@@ -22,6 +25,60 @@ impl Worldspawn {
     }
 }
 
+/// Where the map places an enemy for co-op, added to the enemy spawn point classes
+#[derive(Component, Debug, Clone, Copy)]
+pub struct EnemySpawnPoint(pub EnemyKind);
+
+/// Where the map places a key for co-op, added to the key classes
+#[derive(Component, Debug, Clone, Copy)]
+pub struct KeySpawnPoint(pub KeyColor);
+
+/// `player_spawn_point` in the maps, where the players and the bots that fight them (re)spawn
+#[point_class(base(Transform))]
+pub struct PlayerSpawnPoint;
+
+/// Declares a point class of the map that inserts `$marker` when it is spawned
+macro_rules! marker_point_class {
+    ($(#[$doc:meta])* $name:ident => $marker:expr) => {
+        $(#[$doc])*
+        #[point_class(base(Transform))]
+        #[component(on_add = Self::on_add)]
+        pub struct $name;
+
+        impl $name {
+            fn on_add(mut world: DeferredWorld, ctx: HookContext) {
+                world.commands().entity(ctx.entity).insert($marker);
+            }
+        }
+    };
+}
+
+marker_point_class!(/// `laser_enemy_spawn_point` in the maps
+    LaserEnemySpawnPoint => EnemySpawnPoint(EnemyKind::Laser));
+marker_point_class!(/// `plasma_enemy_spawn_point` in the maps
+    PlasmaEnemySpawnPoint => EnemySpawnPoint(EnemyKind::Plasma));
+marker_point_class!(/// `fusion_enemy_spawn_point` in the maps
+    FusionEnemySpawnPoint => EnemySpawnPoint(EnemyKind::Fusion));
+marker_point_class!(/// `key_red` in the maps
+    KeyRed => KeySpawnPoint(KeyColor::Red));
+marker_point_class!(/// `key_blue` in the maps
+    KeyBlue => KeySpawnPoint(KeyColor::Blue));
+marker_point_class!(/// `key_yellow` in the maps
+    KeyYellow => KeySpawnPoint(KeyColor::Yellow));
+
+/// The map played when the server isn't given any, the file is `maps/<name>.map`
+pub const DEFAULT_MAP: &str = "m4";
+
+/// The name of the map being played.
+/// Lives on a single entity spawned by the server and replicated to every client, which load the same map.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurrentMap(pub String);
+
+/// The root of the loaded map scene, with the name of the map.
+/// Despawning it removes the map with its colliders and entities.
+#[derive(Component, Debug)]
+pub struct MapScene(pub String);
+
 #[derive(Default)]
 pub struct MapPlugin;
 
@@ -36,15 +93,39 @@ impl Plugin for MapPlugin {
             .default_solid_spawn_hooks(|| SpawnHooks::new().smooth_by_default_angle().convex_collider());
         app.add_plugins(TrenchBroomPlugins(config).build());
         app.override_class::<Worldspawn>();
-        app.add_systems(Startup, load_map_system);
+        app.register_type::<PlayerSpawnPoint>();
+        app.register_type::<LaserEnemySpawnPoint>();
+        app.register_type::<PlasmaEnemySpawnPoint>();
+        app.register_type::<FusionEnemySpawnPoint>();
+        app.register_type::<KeyRed>();
+        app.register_type::<KeyBlue>();
+        app.register_type::<KeyYellow>();
+        app.add_systems(Update, load_map_system);
         //app.add_systems(Update, generate_pathfinding_nodes_system);
         //app.add_systems(Update, draw_pathfinding_graph_system);
     }
 }
 
+/// Load the map the server plays, replacing the previous one
 fn load_map_system(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
+    current_map: Query<&CurrentMap, Changed<CurrentMap>>,
+    map_scenes: Query<(Entity, &MapScene)>,
 ) {
-    commands.spawn(SceneRoot(asset_server.load("maps/m4.map#Scene")));
+    let Some(CurrentMap(map)) = current_map.iter().next() else { return };
+    // joining a server that plays the map we already have
+    if map_scenes.iter().any(|(_, scene)| scene.0 == *map) {
+        return;
+    }
+    for (entity, scene) in map_scenes.iter() {
+        info!("Unloading map {}", scene.0);
+        commands.entity(entity).despawn();
+    }
+    info!("Loading map {map}");
+    commands.spawn((
+        Name::from(format!("Map ({map})")),
+        MapScene(map.clone()),
+        SceneRoot(asset_server.load(format!("maps/{map}.map#Scene"))),
+    ));
 }
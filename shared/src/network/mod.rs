@@ -1 +1,4 @@
 pub(crate) mod protocol;
+
+/// The port servers listen on unless told otherwise
+pub const DEFAULT_PORT: u16 = 5001;
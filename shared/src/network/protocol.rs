@@ -4,11 +4,15 @@ use lightyear::prelude::*;
 use avian3d::prelude::*;
 use lightyear::prelude::input::{leafwing, InputConfig};
 use crate::player::{PlayerRespawnTimer, PlayerShip};
-use crate::prelude::{Damageable, Projectile, UniqueIdentity, WeaponFiredEvent};
-use crate::ships::Ship;
+use crate::prelude::{DamageTaken, Damageable, Energy, HeldKeys, HitConfirmed, Projectile, ShipImpactEvent, UniqueIdentity, WeaponFiredEvent};
+use crate::ships::{Dash, FlightMode, Ship, ShipSelection};
 use crate::weapons::{CurrentWeaponIndex, WeaponInventory};
 use serde::{Deserialize, Serialize};
 use crate::bot::BotShip;
+use crate::chat::ChatMessage;
+use crate::scoreboard::{KillFeedEntry, Scoreboard};
+use crate::game_mode::{KeyPickup, MatchState};
+use crate::map::CurrentMap;
 
 /// Networking model:
 /// - client is predicted
@@ -23,10 +27,37 @@ pub struct ProtocolPlugin;
 
 pub struct WeaponFiredChannel;
 
+/// Channel used by clients to pick their ship
+pub struct ShipSelectionChannel;
+
+/// Channel used to tell clients about ships slamming into things
+pub struct ShipImpactChannel;
+
+/// Channel used for the text chat, in both directions
+pub struct ChatChannel;
+
+/// Channel used to tell players that their shots hit
+pub struct HitConfirmedChannel;
+
+/// Channel used to tell players where the damage they take comes from
+pub struct DamageTakenChannel;
+
+/// Channel used to tell everyone who shot down whom
+pub struct KillFeedChannel;
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Hash, Reflect, Actionlike)]
 pub enum PlayerInput {
     #[actionlike(DualAxis)]
     Look,
+    /// Analog strafe (x) and forward (y) thrust, added to the digital move buttons
+    #[actionlike(DualAxis)]
+    Move,
+    /// Analog up/down thrust, added to `MoveUp` and `MoveDown`
+    #[actionlike(Axis)]
+    MoveVertical,
+    /// Analog roll, added to `RollLeft` and `RollRight`
+    #[actionlike(Axis)]
+    Roll,
     MoveForward,
     MoveBackward,
     MoveLeft,
@@ -44,7 +75,12 @@ pub enum PlayerInput {
     Weapon3,
     Weapon4,
     Weapon5,
+    /// Handled by the HUD and never sent to the server
     ToggleMousePointer,
+    ToggleFlightMode,
+    Dash,
+    /// Held to show the scoreboard, handled by the HUD and never sent to the server
+    ShowScoreboard,
 }
 
 impl Plugin for ProtocolPlugin {
@@ -55,6 +91,36 @@ impl Plugin for ProtocolPlugin {
             ..default()
         }).add_direction(NetworkDirection::ServerToClient);
 
+        app.add_channel::<ShipImpactChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..default()
+        }).add_direction(NetworkDirection::ServerToClient);
+
+        app.add_channel::<ShipSelectionChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        }).add_direction(NetworkDirection::ClientToServer);
+
+        app.add_channel::<HitConfirmedChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedReliable(ReliableSettings::default()),
+            ..default()
+        }).add_direction(NetworkDirection::ServerToClient);
+
+        app.add_channel::<DamageTakenChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..default()
+        }).add_direction(NetworkDirection::ServerToClient);
+
+        app.add_channel::<KillFeedChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        }).add_direction(NetworkDirection::ServerToClient);
+
+        app.add_channel::<ChatChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        }).add_direction(NetworkDirection::Bidirectional);
+
         // Inputs
         app.add_plugins(leafwing::InputPlugin::<PlayerInput> {
             config: InputConfig::<PlayerInput> {
@@ -69,6 +135,25 @@ impl Plugin for ProtocolPlugin {
             .add_direction(NetworkDirection::ServerToClient)
             .add_map_entities();
 
+        app.add_message::<ShipImpactEvent>()
+            .add_direction(NetworkDirection::ServerToClient)
+            .add_map_entities();
+
+        app.add_message::<ShipSelection>()
+            .add_direction(NetworkDirection::ClientToServer);
+
+        app.add_message::<HitConfirmed>()
+            .add_direction(NetworkDirection::ServerToClient);
+
+        app.add_message::<DamageTaken>()
+            .add_direction(NetworkDirection::ServerToClient);
+
+        app.add_message::<KillFeedEntry>()
+            .add_direction(NetworkDirection::ServerToClient);
+
+        app.add_message::<ChatMessage>()
+            .add_direction(NetworkDirection::Bidirectional);
+
         // Components
         app.register_component::<Name>()
             .add_prediction(PredictionMode::Once)
@@ -82,7 +167,8 @@ impl Plugin for ProtocolPlugin {
             .add_interpolation(InterpolationMode::Simple);
         
         app.register_component::<Ship>()
-            .add_prediction(PredictionMode::Once);
+            .add_prediction(PredictionMode::Once)
+            .add_interpolation(InterpolationMode::Once);
 
         app.register_component::<BotShip>()
             .add_interpolation(InterpolationMode::Once);
@@ -118,8 +204,22 @@ impl Plugin for ProtocolPlugin {
             .add_linear_correction_fn();
 
         app.register_component::<UniqueIdentity>();
-        app.register_component::<Damageable>();
+        // predicted so that the HUD can show the health of our own ship
+        app.register_component::<Damageable>()
+            .add_prediction(PredictionMode::Simple);
+        app.register_component::<Energy>()
+            .add_prediction(PredictionMode::Full);
+        app.register_component::<HeldKeys>()
+            .add_prediction(PredictionMode::Simple);
+        app.register_component::<FlightMode>()
+            .add_prediction(PredictionMode::Full);
+        app.register_component::<Dash>()
+            .add_prediction(PredictionMode::Full);
         app.register_component::<CurrentWeaponIndex>()
             .add_prediction(PredictionMode::Full);
+        app.register_component::<Scoreboard>();
+        app.register_component::<MatchState>();
+        app.register_component::<KeyPickup>();
+        app.register_component::<CurrentMap>();
     }
 }
@@ -4,7 +4,7 @@ use leafwing_input_manager::prelude::*;
 use lightyear::connection::client_of::ClientOf;
 use lightyear::prelude::*;
 use serde::{Deserialize, Serialize};
-use crate::{prelude::PlayerInput, ships::{move_ship, Ship, ShipsData}};
+use crate::{energy::Energy, prelude::PlayerInput, ships::{auto_level, dash, move_ship, Dash, FlightMode, Ship, ShipsData}};
 
 pub struct PlayerPlugin;
 
@@ -72,16 +72,30 @@ pub fn move_player(
         &mut AngularVelocity,
         &Ship,
         &ActionState<PlayerInput>,
+        Option<&mut Energy>,
+        Option<&mut FlightMode>,
+        Option<(&mut Dash, &mut ExternalImpulse)>,
     ),
-    Or<(With<Predicted>, With<Replicating>)>>,
+    (Or<(With<Predicted>, With<Replicating>)>, Without<ServerControlled>)>,
     ships_data: Res<ShipsData>,
 ) {
-    for (_player, rotation, mut linear_velocity, mut angular_velocity, ship_index, action_state) in query.iter_mut() {
-        let mut wish_dir = Vec3::ZERO;
-
+    for (_player, rotation, mut linear_velocity, mut angular_velocity, ship_index, action_state, mut energy, flight_mode, mut dash_state) in query.iter_mut() {
+        if let Some((dash_state, _)) = dash_state.as_mut() {
+            dash_state.tick();
+        }
         if let Some(data) = ships_data.ships.get(&ship_index.0) {
+            let flight_mode = match flight_mode {
+                Some(mut flight_mode) => {
+                    if action_state.just_pressed(&PlayerInput::ToggleFlightMode) {
+                        *flight_mode = flight_mode.next();
+                    }
+                    *flight_mode
+                }
+                None => FlightMode::default(),
+            };
 
-            // @todo-brian: Also send the mouse sensitivity to the server, probably just do it thru PlayerInput
+            // the client already applied the mouse sensitivity before buffering the input,
+            // so the server computes the same angular velocity as the predicted ship
             let mouse_data = action_state.axis_pair(&PlayerInput::Look);
             if mouse_data != Vec2::ZERO {
                 let yaw = -mouse_data.x * data.look_rotation_force;
@@ -93,51 +107,63 @@ pub fn move_player(
                 angular_velocity.0 += up * yaw + right * pitch;
             }
 
-            let mut roll_force = 0.0;
-            if action_state.pressed(&PlayerInput::RollLeft) {
-                roll_force -= data.roll_rotation_force;
-            }
-            if action_state.pressed(&PlayerInput::RollRight) {
-                roll_force += data.roll_rotation_force;
-            }
-            
+            let roll_force = data.roll_rotation_force * analog_axis(
+                action_state,
+                action_state.value(&PlayerInput::Roll),
+                &PlayerInput::RollLeft,
+                &PlayerInput::RollRight,
+            );
+
             if roll_force != 0.0 {
                 let forward = rotation.0 * Vec3::NEG_Z;
                 angular_velocity.0 += forward * roll_force;
+            } else if flight_mode == FlightMode::AutoLevel {
+                // only level the ship while the player isn't rolling on purpose
+                auto_level(&fixed_time, &data, rotation, &mut angular_velocity);
             }
 
-            // Accelerate in the direction of the input
-            if action_state.pressed(&PlayerInput::MoveForward) {
-                wish_dir += rotation.0 * Vec3::NEG_Z;
-            }
-            if action_state.pressed(&PlayerInput::MoveBackward) {
-                wish_dir += rotation.0 * Vec3::Z;
-            }
-            if action_state.pressed(&PlayerInput::MoveLeft) {
-                wish_dir += rotation.0 * Vec3::NEG_X;
-            }
-            if action_state.pressed(&PlayerInput::MoveRight) {
-                wish_dir += rotation.0 * Vec3::X;
-            }
-            if action_state.pressed(&PlayerInput::MoveUp) {
-                wish_dir += rotation.0 * Vec3::Y;
-            }
-            if action_state.pressed(&PlayerInput::MoveDown) {
-                wish_dir += rotation.0 * Vec3::NEG_Y;
+            // Accelerate in the direction of the input, analog sticks can push less than the full throttle
+            let move_axis = action_state.axis_pair(&PlayerInput::Move);
+            let strafe = analog_axis(action_state, move_axis.x, &PlayerInput::MoveLeft, &PlayerInput::MoveRight);
+            let forward = analog_axis(action_state, move_axis.y, &PlayerInput::MoveBackward, &PlayerInput::MoveForward);
+            let vertical = analog_axis(
+                action_state,
+                action_state.value(&PlayerInput::MoveVertical),
+                &PlayerInput::MoveDown,
+                &PlayerInput::MoveUp,
+            );
+            let wish_dir = (rotation.0 * Vec3::new(strafe, vertical, -forward)).clamp_length_max(1.0);
+
+            if action_state.just_pressed(&PlayerInput::Dash) {
+                if let Some((mut dash_state, mut external_impulse)) = dash_state {
+                    if dash_state.is_ready()
+                        && energy.as_mut().is_some_and(|energy| energy.try_drain(data.dash.energy_cost, &data.energy)) {
+                        dash(&data, rotation, wish_dir, &mut dash_state, &mut external_impulse);
+                    }
+                }
             }
 
-            let wish_dir = wish_dir.normalize_or_zero();
-            let after_burners = if action_state.pressed(&PlayerInput::AfterBurners) {
+            // afterburners only work while there is energy left to burn
+            let after_burners = if action_state.pressed(&PlayerInput::AfterBurners)
+                && energy.is_some_and(|mut energy| {
+                    energy.try_drain(data.energy.afterburner_drain * fixed_time.delta_secs(), &data.energy)
+                }) {
                 Some(rotation)
             } else {
                 None
             };
 
-            move_ship(&fixed_time, &data, &mut linear_velocity, &mut angular_velocity, wish_dir, after_burners);
+            move_ship(&fixed_time, &data, &mut linear_velocity, &mut angular_velocity, wish_dir, flight_mode, after_burners);
         }
     }
 }
 
+/// Combine an analog axis with the buttons for both of its directions, the result is between -1.0 and 1.0
+fn analog_axis(action_state: &ActionState<PlayerInput>, axis_value: f32, negative: &PlayerInput, positive: &PlayerInput) -> f32 {
+    let digital = action_state.pressed(positive) as i8 - action_state.pressed(negative) as i8;
+    (axis_value + digital as f32).clamp(-1.0, 1.0)
+}
+
 #[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
 // add a Transform for each player (otherwise interpolated players don't get a Transform)
 #[require(Transform)]
@@ -146,3 +172,8 @@ pub struct PlayerShip;
 #[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PlayerRespawnTimer(pub Timer);
 
+/// Added on a player ship while the server flies it instead of the player's inputs,
+/// for example when a bot took over the ship of a disconnected player.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct ServerControlled;
+
@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game_mode::Team;
+use crate::identity::UniqueIdentity;
+
+/// The statistics of a player or a bot during the current match
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerScore {
+    /// Set in team games
+    pub team: Option<Team>,
+    pub kills: u32,
+    pub deaths: u32,
+    /// Ships this player damaged shortly before someone else shot them down
+    pub assists: u32,
+    pub damage_dealt: u32,
+    /// Projectiles fired, a weapon with several barrels fires several projectiles per shot
+    pub shots_fired: u32,
+    /// Projectiles that damaged a ship
+    pub shots_hit: u32,
+}
+
+impl PlayerScore {
+    /// The fraction of the projectiles that hit a ship, between 0.0 and 1.0
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            return 0.0;
+        }
+        (self.shots_hit as f32 / self.shots_fired as f32).min(1.0)
+    }
+}
+
+/// The scores of everyone in the match.
+/// Lives on a single entity spawned by the server and replicated to every client.
+#[derive(Component, Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scoreboard {
+    pub scores: Vec<(UniqueIdentity, PlayerScore)>,
+}
+
+impl Scoreboard {
+    pub fn get(&self, identity: &UniqueIdentity) -> Option<&PlayerScore> {
+        self.scores.iter()
+            .find(|(other, _)| other == identity)
+            .map(|(_, score)| score)
+    }
+
+    pub fn get_mut(&mut self, identity: &UniqueIdentity) -> Option<&mut PlayerScore> {
+        self.scores.iter_mut()
+            .find(|(other, _)| other == identity)
+            .map(|(_, score)| score)
+    }
+
+    /// The score of `identity`, added to the scoreboard if it wasn't there yet
+    pub fn get_or_insert(&mut self, identity: UniqueIdentity) -> &mut PlayerScore {
+        let index = match self.scores.iter().position(|(other, _)| *other == identity) {
+            Some(index) => index,
+            None => {
+                self.scores.push((identity, PlayerScore::default()));
+                self.scores.len() - 1
+            }
+        };
+        &mut self.scores[index].1
+    }
+
+    pub fn remove(&mut self, identity: &UniqueIdentity) {
+        self.scores.retain(|(other, _)| other != identity);
+    }
+
+    /// Start a new match, everyone keeps their line and their team
+    pub fn reset(&mut self) {
+        for (_, score) in self.scores.iter_mut() {
+            *score = PlayerScore {
+                team: score.team,
+                ..default()
+            };
+        }
+    }
+
+    /// The scores from best to worst: most kills first, then fewest deaths
+    pub fn ranked(&self) -> Vec<&(UniqueIdentity, PlayerScore)> {
+        let mut ranked = self.scores.iter().collect::<Vec<_>>();
+        ranked.sort_by(|(_, a), (_, b)| b.kills.cmp(&a.kills)
+            .then(a.deaths.cmp(&b.deaths))
+            .then(b.assists.cmp(&a.assists)));
+        ranked
+    }
+}
+
+/// Sent by the server to everyone when a ship is shot down, for the kill feed.
+/// Triggered on the clients once received.
+#[derive(Event, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KillFeedEntry {
+    /// `None` if the ship crashed into something
+    pub killer: Option<UniqueIdentity>,
+    pub victim: UniqueIdentity,
+    /// The weapon of the killer, the name is in the `WeaponsData`
+    pub weapon_index: Option<u32>,
+}
@@ -1,4 +1,4 @@
-use avian3d::prelude::{AngularVelocity, CoefficientCombine, Collider, CollisionLayers, Friction, LinearVelocity, RigidBody, Rotation};
+use avian3d::prelude::{AngularVelocity, CoefficientCombine, Collider, CollisionEventsEnabled, CollisionLayers, ExternalImpulse, Friction, LinearVelocity, Mass, NoAutoMass, Restitution, RigidBody, Rotation};
 use bevy::ecs::bundle::Bundle;
 use bevy::prelude::*;
 use bevy::platform::collections::HashMap;
@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 // NOTE: Everything inside this module is shared code between the player and the bot.
 // Since every moveable "character" in our game is a ship of some kind.
 
-use crate::{bot::BotBehavior, physics::GameLayer};
+use crate::{bot::BotBehavior, data::audio::SoundEmitterBehavior, energy::EnergyBehavior, game_mode::GameMode, impacts::{CollisionDamageBehavior, PreImpactVelocity}, physics::GameLayer, weapons::WeaponId};
 pub type ShipId = u32;
 
 #[derive(Component, Serialize, Deserialize, PartialEq, Debug, Default,Eq, Hash, Clone, Copy)]
@@ -18,15 +18,40 @@ pub struct ShipPlugin;
 impl Plugin for ShipPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ConfigAssetLoaderPlugin::<ShipsData>::new("data/ships.ron"));
+        app.init_resource::<ShipColliders>();
+        app.add_systems(PreUpdate, build_ship_colliders_system.run_if(resource_exists::<ShipsData>));
     }
 }
 
 #[derive(Asset, Resource, Default, TypePath, Debug, Deserialize)]
 pub struct ShipsData {
+    /// The ship players fly if they didn't pick one
     pub player_ship: ShipId,
+    /// The ships players are allowed to pick in each game mode.
+    /// If a game mode is missing, players can only fly the `player_ship`.
+    pub selectable_ships: HashMap<GameMode, Vec<ShipId>>,
     pub ships: HashMap<ShipId, ShipBehavior>,
 }
 
+impl ShipsData {
+    /// Whether players are allowed to fly this ship in the given game mode
+    pub fn is_selectable(&self, ship_id: ShipId, game_mode: GameMode) -> bool {
+        if !self.ships.contains_key(&ship_id) {
+            return false;
+        }
+        match self.selectable_ships.get(&game_mode) {
+            Some(selectable) => selectable.contains(&ship_id),
+            None => ship_id == self.player_ship,
+        }
+    }
+}
+
+/// Sent by a client to pick the ship they will fly the next time they spawn
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShipSelection {
+    pub ship_id: ShipId,
+}
+
 #[derive(Default, TypePath, Debug, Deserialize)]
 pub struct ShipBehavior {
     pub name: String,
@@ -39,41 +64,336 @@ pub struct ShipBehavior {
     pub max_rotation_speed: f32,
     pub roll_rotation_force: f32,
     pub rotation_damping: f32,
+    pub physics: ShipPhysicsBehavior,
+    pub energy: EnergyBehavior,
+    pub flight_assist: FlightAssistBehavior,
+    pub collision_damage: CollisionDamageBehavior,
+    pub dash: DashBehavior,
+    /// The sound played when the ship slams into something
+    pub impact_sound: Option<SoundEmitterBehavior>,
+    /// The weapons the ship spawns with
+    pub loadout: Vec<WeaponId>,
     pub bot_behavior: BotBehavior,
 }
 
+/// How a ship flies, toggled by the player with [`PlayerInput::ToggleFlightMode`](crate::prelude::PlayerInput::ToggleFlightMode).
+/// This is predicted, so it must only be modified in FixedUpdate.
+#[derive(Component, Serialize, Deserialize, PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum FlightMode {
+    /// Drag slows the ship down and its rotation is damped
+    #[default]
+    Assisted,
+    /// No drag and no rotation damping, the ship keeps drifting until you counter-thrust
+    Newtonian,
+    /// Same as assisted, but the ship rolls back so that its up vector matches the reference up vector
+    AutoLevel,
+    /// The flight computer fires the thrusters to match the velocity you are asking for, and brings the ship to a stop when you let go
+    VelocityMatching,
+}
+
+impl FlightMode {
+    pub fn next(self) -> Self {
+        match self {
+            FlightMode::Assisted => FlightMode::Newtonian,
+            FlightMode::Newtonian => FlightMode::AutoLevel,
+            FlightMode::AutoLevel => FlightMode::VelocityMatching,
+            FlightMode::VelocityMatching => FlightMode::Assisted,
+        }
+    }
+}
+
+/// Tuning of the flight modes
+#[derive(TypePath, Debug, Clone, Deserialize)]
+pub struct FlightAssistBehavior {
+    /// Rotation damping used in newtonian mode, 0.0 means the ship spins forever
+    pub newtonian_rotation_damping: f32,
+    /// The up vector auto-level rolls the ship towards
+    pub auto_level_up: Vec3,
+    /// How hard auto-level rolls the ship back, per radian of roll error
+    pub auto_level_strength: f32,
+    /// The speed the velocity matching assist tries to reach in the wished direction
+    pub velocity_match_speed: f32,
+    /// How fast the velocity matching assist corrects the velocity, per second
+    pub velocity_match_strength: f32,
+}
+
+impl Default for FlightAssistBehavior {
+    fn default() -> Self {
+        Self {
+            newtonian_rotation_damping: 0.0,
+            auto_level_up: Vec3::Y,
+            auto_level_strength: 2.0,
+            velocity_match_speed: 15.0,
+            velocity_match_strength: 4.0,
+        }
+    }
+}
+
+/// A short burst of speed to dodge projectiles
+#[derive(TypePath, Debug, Clone, Deserialize)]
+pub struct DashBehavior {
+    /// The velocity change (in m/s) of a dash
+    pub strength: f32,
+    /// The number of ticks after a dash before the ship can dash again
+    pub cooldown_ticks: u16,
+    /// The energy a dash costs
+    pub energy_cost: f32,
+}
+
+impl Default for DashBehavior {
+    fn default() -> Self {
+        Self {
+            strength: 20.0,
+            cooldown_ticks: 64,
+            energy_cost: 20.0,
+        }
+    }
+}
+
+/// The dash state of a ship.
+/// The cooldown counts down once per fixed tick and is predicted, so that the client and the server agree exactly on when the ship can dash again.
+#[derive(Component, Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
+pub struct Dash {
+    /// The number of ticks left before the ship can dash again
+    pub cooldown_ticks_left: u16,
+}
+
+impl Dash {
+    pub fn is_ready(&self) -> bool {
+        self.cooldown_ticks_left == 0
+    }
+
+    /// Count down the cooldown, called once per fixed tick
+    pub fn tick(&mut self) {
+        self.cooldown_ticks_left = self.cooldown_ticks_left.saturating_sub(1);
+    }
+}
+
+/// Dash in the given direction, or forward if there is no direction.
+/// The dash goes through the [`ExternalImpulse`] so that it is applied by the physics step like any other impulse.
+pub fn dash(
+    behavior: &ShipBehavior,
+    rotation: &Rotation,
+    wish_dir: Vec3,
+    dash: &mut Dash,
+    external_impulse: &mut ExternalImpulse,
+) {
+    let dir = if wish_dir == Vec3::ZERO {
+        rotation.0 * Vec3::NEG_Z
+    } else {
+        wish_dir.normalize()
+    };
+    // the strength is a velocity change, so it feels the same no matter how heavy the ship is
+    external_impulse.apply_impulse(dir * behavior.dash.strength * behavior.physics.mass);
+    dash.cooldown_ticks_left = behavior.dash.cooldown_ticks;
+}
+
+/// The physical properties of a ship.
+/// Every peer builds the ship's physics from this, so that the server, the predicted and the interpolated ships all collide the same way.
+#[derive(TypePath, Debug, Clone, Deserialize)]
+pub struct ShipPhysicsBehavior {
+    pub collider: ShipColliderShape,
+    pub mass: f32,
+    /// Keep this low so that ships slide along walls instead of rolling
+    pub dynamic_friction: f32,
+    pub static_friction: f32,
+    /// How much the ship bounces off walls and other ships, between 0.0 and 1.0
+    pub restitution: f32,
+}
+
+impl Default for ShipPhysicsBehavior {
+    fn default() -> Self {
+        Self {
+            collider: ShipColliderShape::Sphere { radius: 0.5 },
+            mass: 0.5,
+            dynamic_friction: 0.01,
+            static_friction: 0.1,
+            restitution: 0.0,
+        }
+    }
+}
+
+#[derive(TypePath, Debug, Clone, Deserialize)]
+pub enum ShipColliderShape {
+    Sphere {
+        radius: f32,
+    },
+    /// A capsule aligned with the forward axis of the ship
+    Capsule {
+        radius: f32,
+        length: f32,
+    },
+    /// Several shapes, each offset from the center of the ship
+    Compound(Vec<ShipColliderPart>),
+    /// The convex hull of the given points
+    ConvexHull(Vec<Vec3>),
+    /// The convex hull of a mesh asset, e.g. "models/ship.gltf#Mesh0/Primitive0".
+    /// The mesh has to be loadable on the server too, since the server simulates the ship.
+    ConvexHullFromModel(String),
+}
+
+#[derive(TypePath, Debug, Clone, Deserialize)]
+pub struct ShipColliderPart {
+    pub offset: Vec3,
+    pub shape: ShipColliderShape,
+}
+
+impl ShipColliderShape {
+    /// Build the collider for this shape.
+    /// Returns `None` if it depends on a mesh that is not loaded yet.
+    fn build(&self, meshes: &Assets<Mesh>, model_handles: &HashMap<String, Handle<Mesh>>) -> Option<Collider> {
+        match self {
+            ShipColliderShape::Sphere { radius } => Some(Collider::sphere(*radius)),
+            ShipColliderShape::Capsule { radius, length } => {
+                let half_length = Vec3::Z * length / 2.0;
+                Some(Collider::capsule_endpoints(*radius, -half_length, half_length))
+            }
+            ShipColliderShape::Compound(parts) => {
+                let mut shapes = Vec::with_capacity(parts.len());
+                for part in parts {
+                    shapes.push((part.offset, Quat::IDENTITY, part.shape.build(meshes, model_handles)?));
+                }
+                Some(Collider::compound(shapes))
+            }
+            ShipColliderShape::ConvexHull(points) => {
+                let collider = Collider::convex_hull(points.clone());
+                if collider.is_none() {
+                    error!("Could not build a convex hull from the points: {:?}", points);
+                }
+                collider
+            }
+            ShipColliderShape::ConvexHullFromModel(path) => {
+                let mesh = meshes.get(model_handles.get(path)?)?;
+                Collider::convex_hull_from_mesh(mesh)
+            }
+        }
+    }
+
+    fn model_paths<'a>(&'a self, paths: &mut Vec<&'a str>) {
+        match self {
+            ShipColliderShape::Compound(parts) => {
+                for part in parts {
+                    part.shape.model_paths(paths);
+                }
+            }
+            ShipColliderShape::ConvexHullFromModel(path) => paths.push(path),
+            _ => {}
+        }
+    }
+}
+
+/// The colliders of every ship, built once from the [`ShipsData`] so that all ships of a kind share the exact same shape.
+/// A ship whose collider isn't built yet (because its model is still loading) can't be spawned.
+#[derive(Resource, Default)]
+pub struct ShipColliders {
+    colliders: HashMap<ShipId, Collider>,
+    model_handles: HashMap<String, Handle<Mesh>>,
+}
+
+impl ShipColliders {
+    pub fn get(&self, ship_id: ShipId) -> Option<Collider> {
+        self.colliders.get(&ship_id).cloned()
+    }
+}
+
+/// (Re)build the ship colliders when the ships data changes, or when a model they depend on finishes loading
+fn build_ship_colliders_system(
+    ships_data: Res<ShipsData>,
+    mut ship_colliders: ResMut<ShipColliders>,
+    asset_server: Res<AssetServer>,
+    meshes: Res<Assets<Mesh>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+) {
+    let ship_colliders = ship_colliders.as_mut();
+    if ships_data.is_changed() {
+        ship_colliders.colliders.clear();
+        for ship in ships_data.ships.values() {
+            let mut paths = vec![];
+            ship.physics.collider.model_paths(&mut paths);
+            for path in paths {
+                ship_colliders.model_handles
+                    .entry(path.to_string())
+                    .or_insert_with(|| asset_server.load(path.to_string()));
+            }
+        }
+    } else {
+        let model_loaded = mesh_events.read().any(|event| matches!(event, AssetEvent::LoadedWithDependencies { .. }));
+        if !model_loaded || ship_colliders.colliders.len() == ships_data.ships.len() {
+            return;
+        }
+    }
+
+    for (ship_id, ship) in ships_data.ships.iter() {
+        if ship_colliders.colliders.contains_key(ship_id) {
+            continue;
+        }
+        if let Some(collider) = ship.physics.collider.build(&meshes, &ship_colliders.model_handles) {
+            ship_colliders.colliders.insert(*ship_id, collider);
+        }
+    }
+}
+
 pub fn move_ship(
     fixed_time: &Time<Fixed>,
     behavior: &ShipBehavior,
     linear_velocity: &mut LinearVelocity,
     angular_velocity: &mut AngularVelocity,
+    // the length of the wish direction (between 0.0 and 1.0) is the throttle, so analog sticks can fly slower
     wish_dir: Vec3,
+    flight_mode: FlightMode,
     // if we're using afterburners, we need to know the rotation of the ship to accelerate in the correct direction
     after_burners: Option<&Rotation>,
 ) {
-    angular_velocity.0 *= 1.0 - behavior.rotation_damping;
+    let rotation_damping = match flight_mode {
+        FlightMode::Newtonian => behavior.flight_assist.newtonian_rotation_damping,
+        _ => behavior.rotation_damping,
+    };
+    angular_velocity.0 *= 1.0 - rotation_damping;
             
+    let throttle = wish_dir.length().min(1.0);
+    let wish_dir = wish_dir.normalize_or_zero();
+
     if angular_velocity.length_squared() > behavior.max_rotation_speed * behavior.max_rotation_speed {
         angular_velocity.0 = angular_velocity.normalize() * behavior.max_rotation_speed;
     }
-    
-    // apply drag
-    linear_velocity.0 = apply_drag(
-        linear_velocity.0,
-        linear_velocity.length(),
-        behavior.drag, 
-        fixed_time.delta_secs()
-    );
-
-    // apply acceleration
-    let current_speed = linear_velocity.dot(wish_dir);
-    linear_velocity.0 += accelerate(
-        wish_dir, 
-        behavior.base_speed,
-        current_speed,
-        behavior.accel_speed,
-        fixed_time.delta_secs()
-    );
+
+    match flight_mode {
+        FlightMode::Assisted | FlightMode::AutoLevel => {
+            // apply drag
+            linear_velocity.0 = apply_drag(
+                linear_velocity.0,
+                linear_velocity.length(),
+                behavior.drag,
+                fixed_time.delta_secs()
+            );
+
+            // apply acceleration
+            let current_speed = linear_velocity.dot(wish_dir);
+            linear_velocity.0 += accelerate(
+                wish_dir,
+                behavior.base_speed * throttle,
+                current_speed,
+                behavior.accel_speed,
+                fixed_time.delta_secs()
+            );
+        }
+        FlightMode::Newtonian => {
+            // no drag, the ship only slows down if we thrust the other way
+            let current_speed = linear_velocity.dot(wish_dir);
+            linear_velocity.0 += accelerate(
+                wish_dir,
+                behavior.base_speed * throttle,
+                current_speed,
+                behavior.accel_speed,
+                fixed_time.delta_secs()
+            );
+        }
+        FlightMode::VelocityMatching => {
+            let target_velocity = wish_dir * behavior.flight_assist.velocity_match_speed * throttle;
+            let blend = (behavior.flight_assist.velocity_match_strength * fixed_time.delta_secs()).min(1.0);
+            linear_velocity.0 = linear_velocity.0.lerp(target_velocity, blend);
+        }
+    }
 
     // afterburners accelerate you forward
     if let Some(rotation) = after_burners {
@@ -89,9 +409,29 @@ pub fn move_ship(
     }
 }
 
+/// Roll the ship back so that its up vector lines up with the reference up vector of the auto-level flight mode.
+/// The roll is corrected around the ship's forward axis only, so it doesn't fight the player's pitch and yaw.
+pub fn auto_level(
+    fixed_time: &Time<Fixed>,
+    behavior: &ShipBehavior,
+    rotation: &Rotation,
+    angular_velocity: &mut AngularVelocity,
+) {
+    let forward = rotation.0 * Vec3::NEG_Z;
+    let up = rotation.0 * Vec3::Y;
+    // the reference up vector, projected on the plane the ship can roll in
+    let target_up = behavior.flight_assist.auto_level_up.reject_from_normalized(forward).normalize_or_zero();
+    if target_up == Vec3::ZERO {
+        // we are looking straight up or down, there is no sensible roll to go back to
+        return;
+    }
+    let roll_error = up.cross(target_up).dot(forward).atan2(up.dot(target_up));
+    angular_velocity.0 += forward * roll_error * behavior.flight_assist.auto_level_strength * fixed_time.delta_secs();
+}
+
 /// Shared components for collision, physics, etc.
-/// @todo-brian: probably want to load data from a .ron, like the friction values, because we may want that control per ship.
-pub fn get_shared_ship_components(shape: Collider) -> impl Bundle {
+/// The collider comes from [`ShipColliders`], the rest from the ship's [`ShipPhysicsBehavior`].
+pub fn get_shared_ship_components(shape: Collider, physics: &ShipPhysicsBehavior) -> impl Bundle {
     (
         shape,
         RigidBody::Dynamic,
@@ -106,11 +446,17 @@ pub fn get_shared_ship_components(shape: Collider) -> impl Bundle {
             ]
         ),
         Friction {
-            // We have little friction so that we can slide along walls instead of rolling.
-            dynamic_coefficient: 0.01,
-            static_coefficient: 0.1,
+            dynamic_coefficient: physics.dynamic_friction,
+            static_coefficient: physics.static_friction,
             combine_rule: CoefficientCombine::Min,
         },
+        Restitution::new(physics.restitution),
+        // use the mass from the data instead of computing it from the collider's volume
+        Mass(physics.mass),
+        NoAutoMass,
+        // needed to apply collision damage
+        CollisionEventsEnabled,
+        PreImpactVelocity::default(),
     )
 }
 
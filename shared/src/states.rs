@@ -14,4 +14,6 @@ pub enum AppState {
 pub enum PlayingSubState {
     Playing,
     Menu,
+    /// Typing in the chat box, the ship doesn't react to the keys
+    Chat,
 }
\ No newline at end of file
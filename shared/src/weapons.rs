@@ -108,6 +108,19 @@ pub struct ProjectileHitEvent {
     pub weapon_index: u32,
     pub projectile_entity: Entity,
     pub entity_hit: Option<Entity>,
+    /// Where the projectile hit
+    pub position: Vec3,
+}
+
+/// Sent by the server to a player when one of their projectiles damaged something,
+/// so that their HUD can show a hit marker. Triggered on the shooter's client once received.
+#[derive(Event, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HitConfirmed {
+    /// The health the target actually lost
+    pub damage: u16,
+    /// The hit brought the target's health to 0
+    pub killed: bool,
+    pub position: Vec3,
 }
 
 #[derive(Component, Serialize, Deserialize, PartialEq, Clone, Debug)]